@@ -0,0 +1,511 @@
+//! Plays the game in an ANSI terminal, with the same rules and options as
+//! the windowed version.
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Color, Print, SetAttribute, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use minesweeper_rs::board::{
+    Board, BoardConfig, GameView, IndexHelper, MineGenerationError, MineState, MoveOutcome,
+    TileCoordinate,
+};
+use minesweeper_rs::input::{Command, Direction, Key, KeyBindings};
+use minesweeper_rs::mbf;
+use minesweeper_rs::options::Options;
+use minesweeper_rs::stats::{ClickCounts, GameStats, Stopwatch};
+use minesweeper_rs::topology::Topology;
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Duration;
+
+// How often the clock is redrawn while waiting for a key.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug)]
+enum TermError {
+    Io(io::Error),
+    Generation(MineGenerationError),
+}
+
+impl fmt::Display for TermError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TermError::Io(error) => write!(f, "{}", error),
+            TermError::Generation(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for TermError {}
+
+impl From<io::Error> for TermError {
+    fn from(error: io::Error) -> Self {
+        TermError::Io(error)
+    }
+}
+
+impl From<MineGenerationError> for TermError {
+    fn from(error: MineGenerationError) -> Self {
+        TermError::Generation(error)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Cell {
+    Hidden(MineState),
+    Mine,
+    Count(i32),
+}
+
+/// Keeps what each tile shows, so the whole board can be drawn at once.
+struct TerminalView {
+    index_helper: IndexHelper,
+    cells: Vec<Cell>,
+}
+
+impl TerminalView {
+    fn new(config: &BoardConfig) -> Self {
+        Self {
+            index_helper: IndexHelper::new(config.width(), config.height()),
+            cells: vec![Cell::Hidden(MineState::Empty); config.tile_count()],
+        }
+    }
+
+    fn cell(&self, tile_coordinate: &TileCoordinate) -> Cell {
+        self.cells[self.index(tile_coordinate)]
+    }
+
+    fn index(&self, tile_coordinate: &TileCoordinate) -> usize {
+        self.index_helper
+            .compute_index(tile_coordinate.x, tile_coordinate.y)
+    }
+}
+
+impl GameView for TerminalView {
+    type Error = TermError;
+
+    fn update_tile_with_state(
+        &mut self,
+        tile_coordinate: &TileCoordinate,
+        mine_state: MineState,
+    ) -> Result<(), TermError> {
+        let index = self.index(tile_coordinate);
+        self.cells[index] = Cell::Hidden(mine_state);
+        Ok(())
+    }
+
+    fn update_tile_as_mine(&mut self, tile_coordinate: &TileCoordinate) -> Result<(), TermError> {
+        let index = self.index(tile_coordinate);
+        self.cells[index] = Cell::Mine;
+        Ok(())
+    }
+
+    fn update_tile_with_mine_count(
+        &mut self,
+        tile_coordinate: &TileCoordinate,
+        num_mines: i32,
+    ) -> Result<(), TermError> {
+        let index = self.index(tile_coordinate);
+        self.cells[index] = Cell::Count(num_mines);
+        Ok(())
+    }
+}
+
+struct Game {
+    board: Board,
+    view: TerminalView,
+    cursor: TileCoordinate,
+    stopwatch: Stopwatch,
+    clicks: ClickCounts,
+    game_stats: Option<GameStats>,
+    key_bindings: KeyBindings,
+}
+
+impl Game {
+    fn new(options: &Options, seed: u64) -> Self {
+        let mut board = Board::new(options.config, seed);
+        board.set_first_click_policy(options.first_click_policy);
+        board.set_generation_mode(options.generation_mode);
+        board.set_topology(options.topology);
+        board.set_max_mines_per_tile(options.max_mines_per_tile);
+        board.set_lives(options.lives);
+        board.set_practice_mode(options.practice_mode);
+
+        // Terminal players also get to move the way vi does.
+        let mut key_bindings = KeyBindings::default();
+        for (key, direction) in [
+            ('h', Direction::Left),
+            ('j', Direction::Down),
+            ('k', Direction::Up),
+            ('l', Direction::Right),
+        ] {
+            key_bindings.bind(Key::Char(key), Command::MoveSelection(direction));
+        }
+        for (key, command) in [
+            (Key::Char('q'), Command::Quit),
+            (Key::Escape, Command::Quit),
+            (Key::Ctrl('c'), Command::Quit),
+            (Key::Char('u'), Command::Undo),
+            (Key::Char('r'), Command::Redo),
+        ] {
+            key_bindings.bind(key, command);
+        }
+
+        Self {
+            view: TerminalView::new(&options.config),
+            cursor: TileCoordinate {
+                x: options.config.width() / 2,
+                y: options.config.height() / 2,
+            },
+            board,
+            stopwatch: Stopwatch::default(),
+            clicks: ClickCounts::default(),
+            game_stats: None,
+            key_bindings,
+        }
+    }
+
+    fn new_game(&mut self) {
+        let config = self.board.config();
+        self.board.reset(config, rand::random());
+        self.view = TerminalView::new(&config);
+        self.stopwatch = Stopwatch::default();
+        self.clicks = ClickCounts::default();
+        self.game_stats = None;
+    }
+
+    fn move_cursor(&mut self, dx: i32, dy: i32) {
+        let config = self.board.config();
+        let (x, y) = (self.cursor.x + dx, self.cursor.y + dy);
+        if self.board.topology().wraps {
+            self.cursor.x = x.rem_euclid(config.width());
+            self.cursor.y = y.rem_euclid(config.height());
+        } else {
+            self.cursor.x = x.clamp(0, config.width() - 1);
+            self.cursor.y = y.clamp(0, config.height() - 1);
+        }
+    }
+
+    // Every key press on the board counts towards the stats, even the ones
+    // that don't change anything.
+    fn reveal(&mut self) -> Result<(), TermError> {
+        if self.board.mine_state(&self.cursor) == MineState::Revealed {
+            return self.chord();
+        }
+        self.start_clock();
+        self.clicks.left += 1;
+        if self.board.mine_state(&self.cursor) != MineState::Empty {
+            return Ok(());
+        }
+        let outcome = self.board.sweep(&mut self.view, &self.cursor)?;
+        self.on_move_completed(outcome);
+        Ok(())
+    }
+
+    fn flag(&mut self) -> Result<(), TermError> {
+        self.start_clock();
+        self.clicks.right += 1;
+        if self.board.mine_state(&self.cursor) == MineState::Revealed {
+            return Ok(());
+        }
+        self.board.cycle_mine_state(&mut self.view, &self.cursor)
+    }
+
+    fn chord(&mut self) -> Result<(), TermError> {
+        self.clicks.chord += 1;
+        if self.board.mine_state(&self.cursor) != MineState::Revealed {
+            return Ok(());
+        }
+        let outcome = self.board.chord(&mut self.view, &self.cursor)?;
+        self.on_move_completed(outcome);
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), TermError> {
+        let was_game_over = self.board.is_game_over();
+        if self.board.undo(&mut self.view)? && was_game_over {
+            self.game_stats = None;
+            // Showing the loss uncovered every mine, so put the whole board back.
+            self.board.redraw(&mut self.view)?;
+        }
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<(), TermError> {
+        if let Some(outcome) = self.board.redo(&mut self.view)? {
+            self.on_move_completed(outcome);
+        }
+        Ok(())
+    }
+
+    fn start_clock(&mut self) {
+        if !self.board.is_game_over() {
+            self.stopwatch.start();
+        }
+    }
+
+    fn on_move_completed(&mut self, outcome: MoveOutcome) {
+        if outcome == MoveOutcome::Continue {
+            return;
+        }
+
+        self.stopwatch.stop();
+        self.game_stats = self
+            .board
+            .game_stats(outcome, self.stopwatch.elapsed(), self.clicks);
+
+        if let MoveOutcome::Lost(hit_tile) = outcome {
+            let (mine_indices, _) = self.board.mines_in_spiral_order(&hit_tile);
+            for index in mine_indices {
+                // Flags that were right stay where they are.
+                if !matches!(self.view.cells[index], Cell::Hidden(MineState::Flag(_))) {
+                    self.view.cells[index] = Cell::Mine;
+                }
+            }
+        }
+    }
+
+    fn flags(&self) -> i32 {
+        self.view
+            .cells
+            .iter()
+            .map(|cell| match cell {
+                Cell::Hidden(MineState::Flag(mines)) => *mines,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        let config = self.board.config();
+        let mut status = format!(
+            "{}x{}, {} mines, {} flagged, {}s, seed {}",
+            config.width(),
+            config.height(),
+            config.num_mines(),
+            self.flags(),
+            self.stopwatch.elapsed().as_secs(),
+            self.board.seed()
+        );
+        if self.board.lives() > 1 {
+            status.push_str(&format!(", lives left: {}", self.board.lives_left()));
+        }
+        queue!(
+            out,
+            cursor::MoveTo(0, 0),
+            terminal::Clear(ClearType::All),
+            Print(status),
+        )?;
+
+        for y in 0..config.height() {
+            // Tiles are two columns wide, so shifted rows move over by one.
+            let shift = if self.board.topology().has_shifted_rows() && y % 2 != 0 {
+                1
+            } else {
+                0
+            };
+            queue!(out, cursor::MoveTo(shift, y as u16 + 2))?;
+            for x in 0..config.width() {
+                let tile_coordinate = TileCoordinate { x, y };
+                let (symbol, color) = match self.view.cell(&tile_coordinate) {
+                    Cell::Hidden(MineState::Empty) => ('.', Color::DarkGrey),
+                    Cell::Hidden(MineState::Flag(1)) => ('F', Color::Red),
+                    Cell::Hidden(MineState::Flag(mines)) => (
+                        char::from_digit(mines as u32, 10).unwrap_or('F'),
+                        Color::Red,
+                    ),
+                    Cell::Hidden(MineState::Question) => ('?', Color::Yellow),
+                    Cell::Hidden(MineState::Revealed) => (' ', Color::Reset),
+                    Cell::Mine => ('*', Color::Magenta),
+                    Cell::Count(0) => (' ', Color::Reset),
+                    // Counts past 9 only come up with custom neighborhoods,
+                    // they go on as letters.
+                    Cell::Count(count) => (
+                        char::from_digit(count as u32, 36).unwrap_or('#'),
+                        count_color(count),
+                    ),
+                };
+
+                let selected = tile_coordinate == self.cursor;
+                if selected {
+                    queue!(out, SetAttribute(Attribute::Reverse))?;
+                }
+                queue!(
+                    out,
+                    SetForegroundColor(color),
+                    Print(symbol),
+                    SetForegroundColor(Color::Reset),
+                )?;
+                if selected {
+                    queue!(out, SetAttribute(Attribute::NoReverse))?;
+                }
+                queue!(out, Print(' '))?;
+            }
+        }
+
+        let status = match &self.game_stats {
+            Some(stats) => format!("{}. Press space for a new game.", stats),
+            None if self.board.is_game_over() => "Press space for a new game.".to_owned(),
+            None => String::new(),
+        };
+        let mut help =
+            "Arrows/hjkl/wasd move, space reveals, f flags, c chords, n new game, q quits"
+                .to_owned();
+        if self.board.is_practice_mode() {
+            help.push_str(", u undoes, r redoes");
+        }
+        queue!(
+            out,
+            cursor::MoveTo(0, config.height() as u16 + 3),
+            Print(status),
+            cursor::MoveTo(0, config.height() as u16 + 4),
+            Print(help),
+        )?;
+        out.flush()
+    }
+
+    /// Handles one key press, returning false once the player wants to quit.
+    fn on_key(&mut self, key: KeyEvent) -> Result<bool, TermError> {
+        match key_from_event(key).and_then(|key| self.key_bindings.command(key)) {
+            Some(Command::Quit) => Ok(false),
+            Some(command) => {
+                self.on_command(command)?;
+                Ok(true)
+            }
+            None => Ok(true),
+        }
+    }
+
+    fn on_command(&mut self, command: Command) -> Result<(), TermError> {
+        match command {
+            Command::Undo => self.undo()?,
+            Command::Redo => self.redo()?,
+            // There's nowhere to export boards to from the terminal.
+            Command::ExportBoard | Command::Quit => {}
+            Command::NewGame => self.new_game(),
+            _ if self.board.is_game_over() => {
+                if command == Command::Reveal {
+                    self.new_game();
+                }
+            }
+            Command::MoveSelection(direction) => {
+                let (dx, dy) = direction.offset();
+                self.move_cursor(dx, dy);
+            }
+            Command::Reveal => self.reveal()?,
+            Command::CycleMark => self.flag()?,
+            Command::Chord => self.chord()?,
+        }
+        Ok(())
+    }
+}
+
+fn key_from_event(key: KeyEvent) -> Option<Key> {
+    match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Key::Ctrl(c)),
+        KeyCode::Up => Some(Key::Up),
+        KeyCode::Down => Some(Key::Down),
+        KeyCode::Left => Some(Key::Left),
+        KeyCode::Right => Some(Key::Right),
+        KeyCode::Enter => Some(Key::Enter),
+        KeyCode::Esc => Some(Key::Escape),
+        KeyCode::F(number) => Some(Key::F(number)),
+        KeyCode::Char(c) => Some(Key::Char(c)),
+        _ => None,
+    }
+}
+
+fn count_color(count: i32) -> Color {
+    match count {
+        1 => Color::Blue,
+        2 => Color::Green,
+        3 => Color::Red,
+        4 => Color::DarkBlue,
+        5 => Color::DarkRed,
+        6 => Color::Cyan,
+        7 => Color::White,
+        _ => Color::Grey,
+    }
+}
+
+// Puts the terminal back the way it was, even if the game fails.
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let result = RawTerminal;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(result)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        _ = terminal::disable_raw_mode();
+    }
+}
+
+fn run(mut game: Game) -> Result<(), TermError> {
+    let _terminal = RawTerminal::enter()?;
+    let mut out = io::stdout();
+    loop {
+        game.draw(&mut out)?;
+        if !event::poll(REFRESH_INTERVAL)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !game.on_key(key)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn setup() -> Result<Game, String> {
+    let options =
+        Options::from_args(std::env::args().skip(1)).map_err(|error| error.to_string())?;
+    if options.replay.is_some() {
+        return Err("replays can only be played in the windowed version".to_owned());
+    }
+
+    let seed = options.seed.unwrap_or_else(rand::random);
+    match &options.mbf {
+        Some(path) => {
+            let board = mbf::load(path).map_err(|error| error.to_string())?;
+            let options = Options {
+                config: board.config,
+                // The format only knows about square tiles with edges and a
+                // single mine each.
+                topology: Topology::default(),
+                max_mines_per_tile: 1,
+                ..options
+            };
+            let mut game = Game::new(&options, seed);
+            game.board
+                .preset_mines(board.mines.into_iter().map(i32::from).collect())
+                .map_err(|error| error.to_string())?;
+            Ok(game)
+        }
+        None => Ok(Game::new(&options, seed)),
+    }
+}
+
+fn main() {
+    let game = match setup() {
+        Ok(game) => game,
+        Err(error) => {
+            eprintln!("minesweeper-term: {}", error);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(error) = run(game) {
+        eprintln!("minesweeper-term: {}", error);
+        std::process::exit(1);
+    }
+}
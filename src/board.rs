@@ -0,0 +1,2111 @@
+use crate::solver::{self, VisibleBoard, VisibleTile};
+use crate::stats::{self, ClickCounts, GameStats, ThreeBv};
+use crate::topology::Topology;
+use rand::{seq::index, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum MineState {
+    Empty,
+    /// Flagged as holding this many mines, which is always 1 unless tiles
+    /// can hold more than one mine.
+    Flag(i32),
+    Question,
+    Revealed,
+}
+impl MineState {
+    fn cycle(self, max_mines_per_tile: i32) -> Self {
+        match self {
+            MineState::Empty => MineState::Flag(1),
+            MineState::Flag(mines) if mines < max_mines_per_tile => MineState::Flag(mines + 1),
+            MineState::Flag(_) => MineState::Question,
+            MineState::Question => MineState::Empty,
+            MineState::Revealed => unreachable!("We shouldn't be cycling a revealed tile!"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TileCoordinate {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MoveOutcome {
+    Continue,
+    Won,
+    Lost(TileCoordinate),
+}
+
+/// What a mine or flagged tile turned out to be once the game was lost.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum LossReveal {
+    /// The mine that ended the game.
+    FatalMine,
+    /// A mine that wasn't flagged.
+    Mine,
+    /// A flag on exactly as many mines as it says.
+    CorrectFlag,
+    /// A flag on a tile with fewer or more mines than it says, usually none.
+    WrongFlag,
+}
+
+/// The dimensions of a board and how many mines are hidden in it.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BoardConfig {
+    width: i32,
+    height: i32,
+    num_mines: i32,
+}
+
+impl BoardConfig {
+    pub const BEGINNER: Self = Self {
+        width: 9,
+        height: 9,
+        num_mines: 10,
+    };
+    pub const INTERMEDIATE: Self = Self {
+        width: 16,
+        height: 16,
+        num_mines: 40,
+    };
+    pub const EXPERT: Self = Self {
+        width: 30,
+        height: 16,
+        num_mines: 99,
+    };
+
+    pub const MAX_DIMENSION: i32 = 1000;
+    /// The most mines a single tile can be set up to hold.
+    pub const MAX_MINES_PER_TILE: i32 = 9;
+
+    pub fn custom(width: i32, height: i32, num_mines: i32) -> Result<Self, BoardConfigError> {
+        if !(1..=Self::MAX_DIMENSION).contains(&width)
+            || !(1..=Self::MAX_DIMENSION).contains(&height)
+        {
+            return Err(BoardConfigError::InvalidDimensions { width, height });
+        }
+
+        // The first tile the player reveals is never a mine, so there must
+        // always be at least one tile left over.
+        let max_mines = width * height - 1;
+        if !(0..=max_mines).contains(&num_mines) {
+            return Err(BoardConfigError::InvalidMineCount {
+                num_mines,
+                max_mines,
+            });
+        }
+
+        Ok(Self {
+            width,
+            height,
+            num_mines,
+        })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn num_mines(&self) -> i32 {
+        self.num_mines
+    }
+
+    pub fn tile_count(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+}
+
+impl Default for BoardConfig {
+    fn default() -> Self {
+        Self::INTERMEDIATE
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BoardConfigError {
+    InvalidDimensions { width: i32, height: i32 },
+    InvalidMineCount { num_mines: i32, max_mines: i32 },
+}
+
+impl fmt::Display for BoardConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardConfigError::InvalidDimensions { width, height } => write!(
+                f,
+                "a {}x{} board is not supported, both dimensions must be between 1 and {}",
+                width,
+                height,
+                BoardConfig::MAX_DIMENSION
+            ),
+            BoardConfigError::InvalidMineCount {
+                num_mines,
+                max_mines,
+            } => write!(
+                f,
+                "{} mines requested, but the board can hold between 0 and {}",
+                num_mines, max_mines
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BoardConfigError {}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MineGenerationError {
+    NotEnoughRoom {
+        num_mines: i32,
+        available_tiles: usize,
+        max_mines_per_tile: i32,
+    },
+}
+
+impl fmt::Display for MineGenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MineGenerationError::NotEnoughRoom {
+                num_mines,
+                available_tiles,
+                max_mines_per_tile: 1,
+            } => write!(
+                f,
+                "cannot place {} mines, only {} tiles are available",
+                num_mines, available_tiles
+            ),
+            MineGenerationError::NotEnoughRoom {
+                num_mines,
+                available_tiles,
+                max_mines_per_tile,
+            } => write!(
+                f,
+                "cannot place {} mines, only {} tiles holding up to {} each are available",
+                num_mines, available_tiles, max_mines_per_tile
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MineGenerationError {}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SnapshotError {
+    TileCountMismatch { expected: usize, found: usize },
+    MineCountMismatch { expected: i32, found: i32 },
+    TooManyMinesOnTile { max_mines_per_tile: i32, found: i32 },
+    InvalidFlag { max_mines_per_tile: i32, found: i32 },
+    InvalidMaxMinesPerTile(i32),
+    InvalidLives(i32),
+    RevealedBeforeGeneration,
+    NotEnoughRoom(MineGenerationError),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::TileCountMismatch { expected, found } => write!(
+                f,
+                "the board has {} tiles, but the snapshot has {}",
+                expected, found
+            ),
+            SnapshotError::MineCountMismatch { expected, found } => write!(
+                f,
+                "the board has {} mines, but the snapshot has {}",
+                expected, found
+            ),
+            SnapshotError::TooManyMinesOnTile {
+                max_mines_per_tile,
+                found,
+            } => write!(
+                f,
+                "a tile holds {} mines, but tiles can only hold up to {}",
+                found, max_mines_per_tile
+            ),
+            SnapshotError::InvalidFlag {
+                max_mines_per_tile,
+                found,
+            } => write!(
+                f,
+                "a tile is flagged with {} mines, but flags only go from 1 to {}",
+                found, max_mines_per_tile
+            ),
+            SnapshotError::InvalidMaxMinesPerTile(found) => write!(
+                f,
+                "tiles can hold up to {} mines, but only 1 to {} are allowed",
+                found,
+                BoardConfig::MAX_MINES_PER_TILE
+            ),
+            SnapshotError::InvalidLives(found) => {
+                write!(f, "the game has {} lives, but it needs at least 1", found)
+            }
+            SnapshotError::RevealedBeforeGeneration => {
+                write!(f, "the snapshot reveals tiles before any mines were placed")
+            }
+            SnapshotError::NotEnoughRoom(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// How much of the board around the first tile swept is kept free of mines.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub enum FirstClickPolicy {
+    /// The first tile swept can be a mine.
+    Unprotected,
+    /// The first tile swept is never a mine.
+    #[default]
+    SafeTile,
+    /// The first tile swept and all of its neighbors are never mines, so the
+    /// first sweep always opens up an area.
+    SafeOpening,
+}
+
+impl FirstClickPolicy {
+    /// Checks that the mines fit outside of the tiles this policy keeps free,
+    /// wherever the first tile swept is. Otherwise the first sweep can't place
+    /// them.
+    pub fn check_room(
+        self,
+        config: &BoardConfig,
+        topology: &Topology,
+        max_mines_per_tile: i32,
+    ) -> Result<(), MineGenerationError> {
+        let free_tiles = match self {
+            FirstClickPolicy::Unprotected => 0,
+            FirstClickPolicy::SafeTile => 1,
+            FirstClickPolicy::SafeOpening => topology.max_neighbors(config) + 1,
+        };
+        let available_tiles = config.tile_count().saturating_sub(free_tiles);
+        if config.num_mines() as usize > available_tiles * max_mines_per_tile as usize {
+            return Err(MineGenerationError::NotEnoughRoom {
+                num_mines: config.num_mines(),
+                available_tiles,
+                max_mines_per_tile,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// How a layout of mines is chosen once the first tile is swept.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub enum GenerationMode {
+    #[default]
+    Random,
+    /// Keeps generating layouts, up to `max_attempts` of them, until the solver
+    /// can clear one from the first tile swept without guessing. If none of them
+    /// can be, the last layout is used. The solver only knows about tiles with
+    /// a single mine, so boards where tiles can hold more are always random.
+    NoGuess { max_attempts: u32 },
+}
+
+impl GenerationMode {
+    pub const DEFAULT_NO_GUESS_ATTEMPTS: u32 = 250;
+}
+
+/// Everything needed to continue a game later. Both vectors are in board
+/// index order, and `mines` is `None` until the first tile has been swept.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BoardSnapshot {
+    pub config: BoardConfig,
+    pub seed: u64,
+    pub first_click_policy: FirstClickPolicy,
+    pub generation_mode: GenerationMode,
+    pub topology: Topology,
+    pub max_mines_per_tile: i32,
+    pub lives: i32,
+    /// How many mines each tile holds.
+    pub mines: Option<Vec<i32>>,
+    pub mine_states: Vec<MineState>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+struct TileChange {
+    index: usize,
+    before: MineState,
+    after: MineState,
+}
+
+/// Everything a single reveal, chord or flag cycle changed, so that it can be
+/// undone and redone exactly.
+struct Action {
+    changes: Vec<TileChange>,
+    generated_mines: bool,
+    outcome: MoveOutcome,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum MineGenerationState {
+    Deferred,
+    Generated,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct IndexHelper {
+    width: i32,
+    height: i32,
+}
+
+impl IndexHelper {
+    pub fn new(width: i32, height: i32) -> Self {
+        Self { width, height }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn compute_index(&self, x: i32, y: i32) -> usize {
+        (x * self.height + y) as usize
+    }
+
+    pub fn compute_x_from_index(&self, index: usize) -> i32 {
+        index as i32 / self.height
+    }
+
+    pub fn compute_y_from_index(&self, index: usize) -> i32 {
+        index as i32 % self.height
+    }
+
+    pub fn is_in_bounds(&self, x: i32, y: i32) -> bool {
+        (x >= 0 && x < self.width) && (y >= 0 && y < self.height)
+    }
+}
+
+/// Everything the board needs from a frontend in order to show the game.
+pub trait GameView {
+    type Error: From<MineGenerationError>;
+
+    fn update_tile_with_state(
+        &mut self,
+        tile_coordinate: &TileCoordinate,
+        mine_state: MineState,
+    ) -> Result<(), Self::Error>;
+    fn update_tile_as_mine(&mut self, tile_coordinate: &TileCoordinate) -> Result<(), Self::Error>;
+    fn update_tile_with_mine_count(
+        &mut self,
+        tile_coordinate: &TileCoordinate,
+        num_mines: i32,
+    ) -> Result<(), Self::Error>;
+}
+
+/// The rules of the game, independent of how the board is presented.
+pub struct Board {
+    config: BoardConfig,
+    index_helper: IndexHelper,
+
+    mine_states: Vec<MineState>,
+    mines: Vec<i32>,
+    neighbor_counts: Vec<i32>,
+    mine_generation_state: MineGenerationState,
+    first_click_policy: FirstClickPolicy,
+    generation_mode: GenerationMode,
+    topology: Topology,
+    max_mines_per_tile: i32,
+    lives: i32,
+    seed: u64,
+
+    game_over: bool,
+
+    practice_mode: bool,
+    history: Vec<Action>,
+    undone: Vec<Action>,
+    pending_changes: Vec<TileChange>,
+    last_revealed: Vec<TileCoordinate>,
+    last_restored: Vec<TileCoordinate>,
+}
+
+impl Board {
+    pub fn new(config: BoardConfig, seed: u64) -> Self {
+        let mut result = Self {
+            config,
+            index_helper: IndexHelper::new(config.width, config.height),
+
+            mine_states: Vec::new(),
+            mines: Vec::new(),
+            neighbor_counts: Vec::new(),
+            mine_generation_state: MineGenerationState::Deferred,
+            first_click_policy: FirstClickPolicy::default(),
+            generation_mode: GenerationMode::default(),
+            topology: Topology::default(),
+            max_mines_per_tile: 1,
+            lives: 1,
+            seed,
+
+            game_over: false,
+
+            practice_mode: false,
+            history: Vec::new(),
+            undone: Vec::new(),
+            pending_changes: Vec::new(),
+            last_revealed: Vec::new(),
+            last_restored: Vec::new(),
+        };
+
+        result.reset(config, seed);
+
+        result
+    }
+
+    /// Starts a new game. The same seed, board and first tile swept always
+    /// produce the same layout of mines.
+    pub fn reset(&mut self, config: BoardConfig, seed: u64) {
+        self.config = config;
+        self.seed = seed;
+        self.index_helper = IndexHelper::new(config.width, config.height);
+
+        // The storage follows the dimensions of the new board.
+        let tile_count = config.tile_count();
+        self.mine_states.clear();
+        self.mine_states.resize(tile_count, MineState::Empty);
+        self.mines.clear();
+        self.mines.resize(tile_count, 0);
+        self.neighbor_counts.clear();
+
+        self.game_over = false;
+        self.mine_generation_state = MineGenerationState::Deferred;
+
+        self.history.clear();
+        self.undone.clear();
+        self.last_revealed.clear();
+        self.last_restored.clear();
+    }
+
+    /// Restores a game taken with `snapshot`. The undo history isn't part of a
+    /// snapshot, so it starts out empty.
+    pub fn from_snapshot(snapshot: BoardSnapshot) -> Result<Self, SnapshotError> {
+        let mut board = Board::new(snapshot.config, snapshot.seed);
+        board.first_click_policy = snapshot.first_click_policy;
+        board.generation_mode = snapshot.generation_mode;
+        board.topology = snapshot.topology;
+        if !(1..=BoardConfig::MAX_MINES_PER_TILE).contains(&snapshot.max_mines_per_tile) {
+            return Err(SnapshotError::InvalidMaxMinesPerTile(
+                snapshot.max_mines_per_tile,
+            ));
+        }
+        board.max_mines_per_tile = snapshot.max_mines_per_tile;
+        if snapshot.lives < 1 {
+            return Err(SnapshotError::InvalidLives(snapshot.lives));
+        }
+        board.lives = snapshot.lives;
+
+        let tile_count = snapshot.config.tile_count();
+        if snapshot.mine_states.len() != tile_count {
+            return Err(SnapshotError::TileCountMismatch {
+                expected: tile_count,
+                found: snapshot.mine_states.len(),
+            });
+        }
+        // Cycling the mark never goes past the most mines a tile can hold.
+        if let Some(found) = snapshot.mine_states.iter().find_map(|state| match state {
+            MineState::Flag(mines) if !(1..=board.max_mines_per_tile).contains(mines) => {
+                Some(*mines)
+            }
+            _ => None,
+        }) {
+            return Err(SnapshotError::InvalidFlag {
+                max_mines_per_tile: board.max_mines_per_tile,
+                found,
+            });
+        }
+
+        match snapshot.mines {
+            Some(mines) => {
+                if mines.len() != tile_count {
+                    return Err(SnapshotError::TileCountMismatch {
+                        expected: tile_count,
+                        found: mines.len(),
+                    });
+                }
+                if let Some(found) = mines
+                    .iter()
+                    .copied()
+                    .find(|mines| !(0..=board.max_mines_per_tile).contains(mines))
+                {
+                    return Err(SnapshotError::TooManyMinesOnTile {
+                        max_mines_per_tile: board.max_mines_per_tile,
+                        found,
+                    });
+                }
+                let num_mines = mines.iter().sum();
+                if num_mines != snapshot.config.num_mines() {
+                    return Err(SnapshotError::MineCountMismatch {
+                        expected: snapshot.config.num_mines(),
+                        found: num_mines,
+                    });
+                }
+
+                board.mines = mines;
+                board.compute_neighbor_counts();
+                board.mine_generation_state = MineGenerationState::Generated;
+            }
+            None => {
+                if snapshot.mine_states.contains(&MineState::Revealed) {
+                    return Err(SnapshotError::RevealedBeforeGeneration);
+                }
+                // The mines are still to be placed by the first sweep.
+                board
+                    .first_click_policy
+                    .check_room(&board.config, &board.topology, board.max_mines_per_tile)
+                    .map_err(SnapshotError::NotEnoughRoom)?;
+            }
+        }
+        board.mine_states = snapshot.mine_states;
+
+        if board.lives_left() == 0 {
+            board.game_over = true;
+        } else {
+            board.check_for_win();
+        }
+
+        Ok(board)
+    }
+
+    /// Places the mines up front, one count per tile in board index order, so
+    /// that the first sweep doesn't generate new ones.
+    pub fn preset_mines(&mut self, mines: Vec<i32>) -> Result<(), SnapshotError> {
+        let mut board = Board::from_snapshot(BoardSnapshot {
+            mines: Some(mines),
+            ..self.snapshot()
+        })?;
+        board.practice_mode = self.practice_mode;
+        *self = board;
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> BoardSnapshot {
+        let mines = match self.mine_generation_state {
+            MineGenerationState::Deferred => None,
+            MineGenerationState::Generated => Some(self.mines.clone()),
+        };
+
+        BoardSnapshot {
+            config: self.config,
+            seed: self.seed,
+            first_click_policy: self.first_click_policy,
+            generation_mode: self.generation_mode,
+            topology: self.topology,
+            max_mines_per_tile: self.max_mines_per_tile,
+            lives: self.lives,
+            mines,
+            mine_states: self.mine_states.clone(),
+        }
+    }
+
+    /// Whether anything has happened on the board yet.
+    pub fn is_untouched(&self) -> bool {
+        self.mine_generation_state == MineGenerationState::Deferred
+            && self
+                .mine_states
+                .iter()
+                .all(|state| *state == MineState::Empty)
+    }
+
+    /// Whether the mines have been placed, which happens on the first sweep.
+    pub fn has_mines(&self) -> bool {
+        self.mine_generation_state == MineGenerationState::Generated
+    }
+
+    pub fn config(&self) -> BoardConfig {
+        self.config
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn first_click_policy(&self) -> FirstClickPolicy {
+        self.first_click_policy
+    }
+
+    /// Takes effect the next time mines are generated.
+    pub fn set_first_click_policy(&mut self, policy: FirstClickPolicy) {
+        self.first_click_policy = policy;
+    }
+
+    pub fn generation_mode(&self) -> GenerationMode {
+        self.generation_mode
+    }
+
+    /// Takes effect the next time mines are generated.
+    pub fn set_generation_mode(&mut self, mode: GenerationMode) {
+        self.generation_mode = mode;
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Takes effect the next time mines are placed.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    pub fn max_mines_per_tile(&self) -> i32 {
+        self.max_mines_per_tile
+    }
+
+    /// How many mines a single tile can hold, between 1 and
+    /// `BoardConfig::MAX_MINES_PER_TILE`. Takes effect the next time mines are
+    /// generated.
+    pub fn set_max_mines_per_tile(&mut self, max_mines_per_tile: i32) {
+        self.max_mines_per_tile = max_mines_per_tile.clamp(1, BoardConfig::MAX_MINES_PER_TILE);
+    }
+
+    pub fn lives(&self) -> i32 {
+        self.lives
+    }
+
+    /// How many mines can be hit in a game. Hitting a mine only ends the game
+    /// once it uses up the last life, until then the mine stays uncovered and
+    /// play goes on. Takes effect the next time a mine is hit.
+    pub fn set_lives(&mut self, lives: i32) {
+        self.lives = lives.max(1);
+    }
+
+    /// How many mines have been hit so far in this game.
+    pub fn lives_used(&self) -> i32 {
+        self.mine_states
+            .iter()
+            .zip(&self.mines)
+            .filter(|(state, mines)| **state == MineState::Revealed && **mines > 0)
+            .count() as i32
+    }
+
+    pub fn lives_left(&self) -> i32 {
+        (self.lives - self.lives_used()).max(0)
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.game_over
+    }
+
+    /// The tiles the last sweep or chord uncovered, in the order they were
+    /// revealed. Empty if it didn't change anything.
+    pub fn last_revealed(&self) -> &[TileCoordinate] {
+        &self.last_revealed
+    }
+
+    /// The tiles the last undo or redo changed, in the order it changed
+    /// them. Empty if it didn't change anything.
+    pub fn last_restored(&self) -> &[TileCoordinate] {
+        &self.last_restored
+    }
+
+    pub fn is_practice_mode(&self) -> bool {
+        self.practice_mode
+    }
+
+    /// Undo and redo are only available in practice mode.
+    pub fn set_practice_mode(&mut self, practice_mode: bool) {
+        self.practice_mode = practice_mode;
+    }
+
+    pub fn mine_state(&self, tile_coordinate: &TileCoordinate) -> MineState {
+        self.mine_states[self
+            .index_helper
+            .compute_index(tile_coordinate.x, tile_coordinate.y)]
+    }
+
+    /// The 3BV of the board and how much of it is cleared, once the mines
+    /// have been placed.
+    pub fn three_bv(&self) -> Option<ThreeBv> {
+        match self.mine_generation_state {
+            MineGenerationState::Deferred => None,
+            MineGenerationState::Generated => Some(stats::three_bv(
+                &self.config,
+                self.topology,
+                &self.mines,
+                &self.neighbor_counts,
+                &self.mine_states,
+            )),
+        }
+    }
+
+    /// The stats of a game that the given move just ended, once the mines
+    /// have been placed.
+    pub fn game_stats(
+        &self,
+        outcome: MoveOutcome,
+        time: Duration,
+        clicks: ClickCounts,
+    ) -> Option<GameStats> {
+        self.three_bv().map(|three_bv| GameStats {
+            won: outcome == MoveOutcome::Won,
+            time,
+            three_bv,
+            clicks,
+            lives_used: self.lives_used() as u32,
+        })
+    }
+
+    /// What the player can currently see of the board.
+    pub fn visible_board(&self) -> VisibleBoard {
+        let mut visible_board =
+            VisibleBoard::new(self.config.width, self.config.height, self.config.num_mines)
+                .with_topology(self.topology)
+                .with_max_mines_per_tile(self.max_mines_per_tile);
+        for (index, mine_state) in self.mine_states.iter().enumerate() {
+            let tile = match mine_state {
+                MineState::Revealed if self.mines[index] > 0 => VisibleTile::Flagged,
+                MineState::Revealed => VisibleTile::Revealed(self.neighbor_counts[index]),
+                MineState::Flag(_) => VisibleTile::Flagged,
+                MineState::Empty | MineState::Question => VisibleTile::Unrevealed,
+            };
+            let tile_coordinate = TileCoordinate {
+                x: self.index_helper.compute_x_from_index(index),
+                y: self.index_helper.compute_y_from_index(index),
+            };
+            visible_board.set_tile(&tile_coordinate, tile);
+        }
+        visible_board
+    }
+
+    /// Cycles an unrevealed tile through empty, flag and question mark. When
+    /// tiles can hold more than one mine, the flag counts up to that many
+    /// mines before moving on to the question mark.
+    pub fn cycle_mine_state<V: GameView>(
+        &mut self,
+        view: &mut V,
+        tile_coordinate: &TileCoordinate,
+    ) -> Result<(), V::Error> {
+        let index = self
+            .index_helper
+            .compute_index(tile_coordinate.x, tile_coordinate.y);
+        if self.game_over || self.mine_states[index] == MineState::Revealed {
+            return Ok(());
+        }
+
+        self.record_action(view, |board, view| {
+            let state = board.mine_states[index].cycle(board.max_mines_per_tile);
+            board.set_mine_state(index, state);
+            view.update_tile_with_state(tile_coordinate, state)?;
+            Ok(MoveOutcome::Continue)
+        })?;
+        Ok(())
+    }
+
+    /// Reveals an empty tile, opening up its surroundings if it has no neighboring mines.
+    pub fn sweep<V: GameView>(
+        &mut self,
+        view: &mut V,
+        tile_coordinate: &TileCoordinate,
+    ) -> Result<MoveOutcome, V::Error> {
+        if self.game_over || self.mine_state(tile_coordinate) != MineState::Empty {
+            self.last_revealed.clear();
+            return Ok(MoveOutcome::Continue);
+        }
+
+        self.record_action(view, |board, view| {
+            if board.sweep_from(view, tile_coordinate.x, tile_coordinate.y)?
+                && board.lives_left() == 0
+            {
+                board.game_over = true;
+                Ok(MoveOutcome::Lost(*tile_coordinate))
+            } else {
+                Ok(board.check_for_win())
+            }
+        })
+    }
+
+    /// Reveals the unmarked neighbors of a revealed number if the flags around
+    /// it add up to the number.
+    pub fn chord<V: GameView>(
+        &mut self,
+        view: &mut V,
+        cur_tile: &TileCoordinate,
+    ) -> Result<MoveOutcome, V::Error> {
+        self.record_action(view, |board, view| board.chord_tile(view, cur_tile))
+    }
+
+    /// Takes back the last reveal, chord or flag cycle, including one that
+    /// ended the game. Returns whether anything was undone.
+    pub fn undo<V: GameView>(&mut self, view: &mut V) -> Result<bool, V::Error> {
+        self.last_restored.clear();
+        if !self.practice_mode {
+            return Ok(false);
+        }
+        let action = match self.history.pop() {
+            Some(action) => action,
+            None => return Ok(false),
+        };
+
+        for change in action.changes.iter().rev() {
+            self.mine_states[change.index] = change.before;
+            self.redraw_tile(view, change.index)?;
+            self.last_restored.push(self.coordinate(change.index));
+        }
+        if action.generated_mines {
+            self.mine_generation_state = MineGenerationState::Deferred;
+        }
+        self.game_over = false;
+
+        self.undone.push(action);
+        Ok(true)
+    }
+
+    /// Applies the last undone action again, returning how it ended.
+    pub fn redo<V: GameView>(&mut self, view: &mut V) -> Result<Option<MoveOutcome>, V::Error> {
+        self.last_restored.clear();
+        if !self.practice_mode {
+            return Ok(None);
+        }
+        let action = match self.undone.pop() {
+            Some(action) => action,
+            None => return Ok(None),
+        };
+
+        for change in &action.changes {
+            self.mine_states[change.index] = change.after;
+            self.redraw_tile(view, change.index)?;
+            self.last_restored.push(self.coordinate(change.index));
+        }
+        if action.generated_mines {
+            self.mine_generation_state = MineGenerationState::Generated;
+        }
+        self.game_over = action.outcome != MoveOutcome::Continue;
+
+        let outcome = action.outcome;
+        self.history.push(action);
+        Ok(Some(outcome))
+    }
+
+    /// Updates the view for every tile on the board.
+    pub fn redraw<V: GameView>(&self, view: &mut V) -> Result<(), V::Error> {
+        for index in 0..self.mine_states.len() {
+            self.redraw_tile(view, index)?;
+        }
+        Ok(())
+    }
+
+    fn redraw_tile<V: GameView>(&self, view: &mut V, index: usize) -> Result<(), V::Error> {
+        let tile_coordinate = self.coordinate(index);
+
+        match self.mine_states[index] {
+            MineState::Revealed if self.mines[index] > 0 => {
+                view.update_tile_as_mine(&tile_coordinate)
+            }
+            MineState::Revealed => {
+                view.update_tile_with_mine_count(&tile_coordinate, self.neighbor_counts[index])
+            }
+            state => view.update_tile_with_state(&tile_coordinate, state),
+        }
+    }
+
+    fn coordinate(&self, index: usize) -> TileCoordinate {
+        TileCoordinate {
+            x: self.index_helper.compute_x_from_index(index),
+            y: self.index_helper.compute_y_from_index(index),
+        }
+    }
+
+    fn record_action<V: GameView>(
+        &mut self,
+        view: &mut V,
+        action: impl FnOnce(&mut Self, &mut V) -> Result<MoveOutcome, V::Error>,
+    ) -> Result<MoveOutcome, V::Error> {
+        let was_deferred = self.mine_generation_state == MineGenerationState::Deferred;
+        self.pending_changes.clear();
+
+        let result = action(self, view);
+
+        self.last_revealed = self
+            .pending_changes
+            .iter()
+            .filter(|change| change.after == MineState::Revealed)
+            .map(|change| self.coordinate(change.index))
+            .collect();
+        if !self.pending_changes.is_empty() {
+            let outcome = match &result {
+                Ok(outcome) => *outcome,
+                Err(_) => MoveOutcome::Continue,
+            };
+            self.history.push(Action {
+                changes: std::mem::take(&mut self.pending_changes),
+                generated_mines: was_deferred
+                    && self.mine_generation_state == MineGenerationState::Generated,
+                outcome,
+            });
+            self.undone.clear();
+        }
+
+        result
+    }
+
+    fn set_mine_state(&mut self, index: usize, state: MineState) {
+        self.pending_changes.push(TileChange {
+            index,
+            before: self.mine_states[index],
+            after: state,
+        });
+        self.mine_states[index] = state;
+    }
+
+    fn chord_tile<V: GameView>(
+        &mut self,
+        view: &mut V,
+        cur_tile: &TileCoordinate,
+    ) -> Result<MoveOutcome, V::Error> {
+        if self.game_over || self.mine_generation_state == MineGenerationState::Deferred {
+            return Ok(MoveOutcome::Continue);
+        }
+
+        // Does the current tile have a number in it?
+        let index = self.index_helper.compute_index(cur_tile.x, cur_tile.y);
+        if self.mines[index] > 0
+            || self.neighbor_counts[index] < 1
+            || self.mine_states[index] != MineState::Revealed
+        {
+            // No neighbors, or not revealed, do nothing!
+            return Ok(MoveOutcome::Continue);
+        }
+
+        // See if all mines are marked that are in the surrounding tiles
+        let neighbors: Vec<usize> = self.topology.neighbors(&self.index_helper, index).collect();
+        // Mines that were hit are as good as flagged.
+        let flag_count: i32 = neighbors
+            .iter()
+            .map(|neighbor| match self.mine_states[*neighbor] {
+                MineState::Flag(mines) => mines,
+                MineState::Revealed => self.mines[*neighbor],
+                _ => 0,
+            })
+            .sum();
+        if flag_count != self.neighbor_counts[index] {
+            // Too many or not enough flags
+            return Ok(MoveOutcome::Continue);
+        }
+
+        // OK, go through the neighbors and try and reveal all of them with sweep if they're not flagged
+        for neighbor in neighbors {
+            // Is it unrevealed?  Only click on those spaces
+            if self.mine_states[neighbor] != MineState::Empty {
+                // Already revealed, so don't click
+                continue;
+            }
+            let x = self.index_helper.compute_x_from_index(neighbor);
+            let y = self.index_helper.compute_y_from_index(neighbor);
+            if self.sweep_from(view, x, y)? && self.lives_left() == 0 {
+                self.game_over = true;
+                return Ok(MoveOutcome::Lost(TileCoordinate { x, y }));
+            }
+        }
+
+        Ok(self.check_for_win())
+    }
+
+    /// Builds a queue that contains the indices of the tiles with mines in rings
+    /// spreading out from the given tile, along with the number of those tiles
+    /// found in each ring.
+    /// Each ring is one more step away from the tile, following the topology.
+    pub fn mines_in_spiral_order(
+        &self,
+        center: &TileCoordinate,
+    ) -> (VecDeque<usize>, VecDeque<i32>) {
+        self.spiral_order(center, |index| self.mines[index] > 0)
+    }
+
+    /// Like `mines_in_spiral_order`, but with every tile of the board.
+    pub fn tiles_in_spiral_order(
+        &self,
+        center: &TileCoordinate,
+    ) -> (VecDeque<usize>, VecDeque<i32>) {
+        self.spiral_order(center, |_| true)
+    }
+
+    /// The unrevealed tiles that hold mines, along with how many each holds.
+    pub fn hidden_mines(&self) -> Vec<(TileCoordinate, i32)> {
+        (0..self.mines.len())
+            .filter(|index| {
+                self.mines[*index] > 0 && self.mine_states[*index] != MineState::Revealed
+            })
+            .map(|index| {
+                let tile_coordinate = TileCoordinate {
+                    x: self.index_helper.compute_x_from_index(index),
+                    y: self.index_helper.compute_y_from_index(index),
+                };
+                (tile_coordinate, self.mines[index])
+            })
+            .collect()
+    }
+
+    /// Every tile that holds mines or a flag, along with what it turned out
+    /// to be, for showing the player where a lost game went wrong.
+    pub fn loss_reveal(&self, hit_tile: &TileCoordinate) -> Vec<(TileCoordinate, LossReveal)> {
+        (0..self.mines.len())
+            .filter_map(|index| {
+                let tile_coordinate = TileCoordinate {
+                    x: self.index_helper.compute_x_from_index(index),
+                    y: self.index_helper.compute_y_from_index(index),
+                };
+                let reveal = match self.mine_states[index] {
+                    _ if tile_coordinate == *hit_tile => LossReveal::FatalMine,
+                    MineState::Flag(flagged) if flagged == self.mines[index] => {
+                        LossReveal::CorrectFlag
+                    }
+                    MineState::Flag(_) => LossReveal::WrongFlag,
+                    _ if self.mines[index] > 0 => LossReveal::Mine,
+                    _ => return None,
+                };
+                Some((tile_coordinate, reveal))
+            })
+            .collect()
+    }
+
+    fn spiral_order(
+        &self,
+        center: &TileCoordinate,
+        include: impl Fn(usize) -> bool,
+    ) -> (VecDeque<usize>, VecDeque<i32>) {
+        let center = self.index_helper.compute_index(center.x, center.y);
+        // The center goes first either way, it's the tile everything spreads
+        // out from.
+        let mut indices: VecDeque<usize> = VecDeque::from([center]);
+        let mut tiles_per_ring: VecDeque<i32> = VecDeque::from([1]);
+
+        let mut visited = vec![false; self.mines.len()];
+        visited[center] = true;
+        let mut ring = vec![center];
+        loop {
+            let mut next_ring = Vec::new();
+            for index in ring {
+                for neighbor in self.topology.neighbors(&self.index_helper, index) {
+                    if !visited[neighbor] {
+                        visited[neighbor] = true;
+                        next_ring.push(neighbor);
+                    }
+                }
+            }
+            if next_ring.is_empty() {
+                break;
+            }
+
+            let tiles_before = indices.len();
+            indices.extend(next_ring.iter().filter(|index| include(**index)));
+            let current_tiles_in_ring = (indices.len() - tiles_before) as i32;
+            if current_tiles_in_ring > 0 {
+                tiles_per_ring.push_back(current_tiles_in_ring);
+            }
+            ring = next_ring;
+        }
+
+        // Some neighborhoods don't connect every tile, the tiles that can't be
+        // reached from the center go off last.
+        let tiles_before = indices.len();
+        indices.extend((0..self.mines.len()).filter(|index| include(*index) && !visited[*index]));
+        let unreachable_tiles = (indices.len() - tiles_before) as i32;
+        if unreachable_tiles > 0 {
+            tiles_per_ring.push_back(unreachable_tiles);
+        }
+
+        (indices, tiles_per_ring)
+    }
+
+    fn sweep_from<V: GameView>(&mut self, view: &mut V, x: i32, y: i32) -> Result<bool, V::Error> {
+        if self.mine_generation_state == MineGenerationState::Deferred {
+            // Depending on the policy, we don't want the first thing that the user
+            // clicks to be a mine. Generate mines but avoid putting them where the
+            // user clicked.
+            let excluded_indices = self.first_click_exclusions(x, y);
+            self.generate_mines(view, self.config.num_mines(), &excluded_indices, x, y)?;
+            self.mine_generation_state = MineGenerationState::Generated;
+        }
+
+        let mut hit_mine = false;
+        let mut sweeps: VecDeque<usize> = VecDeque::new();
+        sweeps.push_back(self.index_helper.compute_index(x, y));
+        self.reveal(view, *sweeps.front().unwrap())?;
+
+        while !sweeps.is_empty() {
+            let index = *sweeps.front().unwrap();
+
+            if self.mines[index] > 0 {
+                // We hit a mine, game over
+                hit_mine = true;
+                break;
+            }
+
+            if self.neighbor_counts[index] == 0 {
+                let neighbors: Vec<usize> =
+                    self.topology.neighbors(&self.index_helper, index).collect();
+                for neighbor in neighbors {
+                    self.push_if_unmarked(view, &mut sweeps, neighbor)?;
+                }
+            }
+
+            sweeps.pop_front().unwrap();
+        }
+
+        Ok(hit_mine)
+    }
+
+    fn reveal<V: GameView>(&mut self, view: &mut V, index: usize) -> Result<(), V::Error> {
+        let tile_coordinate = TileCoordinate {
+            x: self.index_helper.compute_x_from_index(index),
+            y: self.index_helper.compute_y_from_index(index),
+        };
+
+        if self.mines[index] > 0 {
+            view.update_tile_as_mine(&tile_coordinate)?;
+        } else {
+            let count = self.neighbor_counts[index];
+            view.update_tile_with_mine_count(&tile_coordinate, count)?;
+        }
+
+        self.set_mine_state(index, MineState::Revealed);
+        Ok(())
+    }
+
+    fn push_if_unmarked<V: GameView>(
+        &mut self,
+        view: &mut V,
+        sweeps: &mut VecDeque<usize>,
+        index: usize,
+    ) -> Result<(), V::Error> {
+        if self.mine_states[index] == MineState::Empty {
+            self.reveal(view, index)?;
+            sweeps.push_back(index);
+        }
+
+        Ok(())
+    }
+
+    fn generate_mines<V: GameView>(
+        &mut self,
+        view: &mut V,
+        num_mines: i32,
+        excluded_indices: &[usize],
+        first_x: i32,
+        first_y: i32,
+    ) -> Result<(), V::Error> {
+        // Pick the mines from every tile that isn't excluded. Sampling without
+        // replacement keeps this fast no matter how dense the board is.
+        let candidates: Vec<usize> = (0..self.config.tile_count())
+            .filter(|index| !excluded_indices.contains(index))
+            .collect();
+        let room = candidates.len() * self.max_mines_per_tile as usize;
+        if num_mines < 0 || num_mines as usize > room {
+            return Err(MineGenerationError::NotEnoughRoom {
+                num_mines,
+                available_tiles: candidates.len(),
+                max_mines_per_tile: self.max_mines_per_tile,
+            }
+            .into());
+        }
+
+        let generation_mode = match self.max_mines_per_tile {
+            1 => self.generation_mode(),
+            _ => GenerationMode::Random,
+        };
+        let attempts = match generation_mode {
+            GenerationMode::Random => 1,
+            GenerationMode::NoGuess { max_attempts } => max_attempts.max(1),
+        };
+        // Every attempt draws from the same stream, so a seed still always
+        // produces the same board.
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        for _attempt in 0..attempts {
+            self.place_mines(&mut rng, &candidates, num_mines as usize);
+
+            if generation_mode == GenerationMode::Random
+                || solver::can_clear_without_guessing(
+                    self.visible_board(),
+                    &self.mines,
+                    &self.neighbor_counts,
+                    &TileCoordinate {
+                        x: first_x,
+                        y: first_y,
+                    },
+                )
+            {
+                break;
+            }
+        }
+
+        // DEBUG
+        if cfg!(feature = "show-mines") {
+            for i in 0..self.mines.len() {
+                if self.mines[i] > 0 {
+                    let x = self.index_helper.compute_x_from_index(i);
+                    let y = self.index_helper.compute_y_from_index(i);
+                    view.update_tile_with_state(&TileCoordinate { x, y }, MineState::Question)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn place_mines(&mut self, rng: &mut ChaCha8Rng, candidates: &[usize], num_mines: usize) {
+        for mines in self.mines.iter_mut() {
+            *mines = 0;
+        }
+
+        // Every candidate has a slot for each mine it can hold, so that with a
+        // single mine per tile the slots are just the candidates.
+        let slots_per_tile = self.max_mines_per_tile as usize;
+        for sample in index::sample(rng, candidates.len() * slots_per_tile, num_mines) {
+            self.mines[candidates[sample / slots_per_tile]] += 1;
+        }
+
+        self.compute_neighbor_counts();
+    }
+
+    fn compute_neighbor_counts(&mut self) {
+        self.neighbor_counts.clear();
+        for i in 0..self.mines.len() {
+            let count = self.get_surrounding_mine_count(i);
+            self.neighbor_counts.push(count);
+        }
+    }
+
+    fn first_click_exclusions(&self, x: i32, y: i32) -> Vec<usize> {
+        let index = self.index_helper.compute_index(x, y);
+        match self.first_click_policy() {
+            FirstClickPolicy::Unprotected => Vec::new(),
+            FirstClickPolicy::SafeTile => vec![index],
+            FirstClickPolicy::SafeOpening => std::iter::once(index)
+                .chain(self.topology.neighbors(&self.index_helper, index))
+                .collect(),
+        }
+    }
+
+    fn get_surrounding_mine_count(&self, index: usize) -> i32 {
+        self.topology
+            .neighbors(&self.index_helper, index)
+            .map(|neighbor| self.mines[neighbor])
+            .sum()
+    }
+
+    fn check_for_win(&mut self) -> MoveOutcome {
+        // Every tile without a mine has to be revealed. Mines that were hit
+        // along the way are revealed too, and don't stand in the way.
+        let won = self
+            .mine_states
+            .iter()
+            .zip(&self.mines)
+            .all(|(state, mines)| *state == MineState::Revealed || *mines > 0);
+        if won {
+            self.game_over = true;
+            MoveOutcome::Won
+        } else {
+            MoveOutcome::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::TileShape;
+
+    // The board only needs somewhere to send its updates.
+    struct NoView;
+
+    impl GameView for NoView {
+        type Error = MineGenerationError;
+
+        fn update_tile_with_state(
+            &mut self,
+            _tile_coordinate: &TileCoordinate,
+            _mine_state: MineState,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn update_tile_as_mine(
+            &mut self,
+            _tile_coordinate: &TileCoordinate,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn update_tile_with_mine_count(
+            &mut self,
+            _tile_coordinate: &TileCoordinate,
+            _num_mines: i32,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    fn tile(x: i32, y: i32) -> TileCoordinate {
+        TileCoordinate { x, y }
+    }
+
+    // A board with its mines already placed on the given tiles.
+    fn board_with_mines(width: i32, height: i32, mines: &[(i32, i32)]) -> Board {
+        let config = BoardConfig::custom(width, height, mines.len() as i32).unwrap();
+        let index_helper = IndexHelper::new(width, height);
+        let mut layout = vec![0; config.tile_count()];
+        for &(x, y) in mines {
+            layout[index_helper.compute_index(x, y)] = 1;
+        }
+        Board::from_snapshot(BoardSnapshot {
+            mines: Some(layout),
+            ..Board::new(config, 0).snapshot()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn sweep_opens_the_area_around_an_empty_tile() {
+        // A wall of mines down the middle stops the flood fill.
+        let wall: Vec<(i32, i32)> = (0..5).map(|y| (2, y)).collect();
+        let mut board = board_with_mines(5, 5, &wall);
+
+        let outcome = board.sweep(&mut NoView, &tile(0, 0)).unwrap();
+
+        assert_eq!(outcome, MoveOutcome::Continue);
+        for y in 0..5 {
+            assert_eq!(board.mine_state(&tile(0, y)), MineState::Revealed);
+            assert_eq!(board.mine_state(&tile(1, y)), MineState::Revealed);
+            assert_eq!(board.mine_state(&tile(2, y)), MineState::Empty);
+            assert_eq!(board.mine_state(&tile(3, y)), MineState::Empty);
+        }
+    }
+
+    #[test]
+    fn sweep_stops_at_numbers() {
+        let mut board = board_with_mines(3, 3, &[(0, 0), (2, 0)]);
+
+        board.sweep(&mut NoView, &tile(1, 1)).unwrap();
+
+        assert_eq!(board.mine_state(&tile(1, 1)), MineState::Revealed);
+        assert_eq!(board.mine_state(&tile(1, 2)), MineState::Empty);
+    }
+
+    #[test]
+    fn chord_needs_the_right_number_of_flags() {
+        let mut board = board_with_mines(3, 3, &[(0, 0), (2, 0)]);
+        board.sweep(&mut NoView, &tile(1, 1)).unwrap();
+
+        // Not enough flags yet.
+        board.cycle_mine_state(&mut NoView, &tile(0, 0)).unwrap();
+        let outcome = board.chord(&mut NoView, &tile(1, 1)).unwrap();
+        assert_eq!(outcome, MoveOutcome::Continue);
+        assert_eq!(board.mine_state(&tile(1, 2)), MineState::Empty);
+
+        board.cycle_mine_state(&mut NoView, &tile(2, 0)).unwrap();
+        let outcome = board.chord(&mut NoView, &tile(1, 1)).unwrap();
+        assert_eq!(outcome, MoveOutcome::Won);
+        assert_eq!(board.mine_state(&tile(1, 2)), MineState::Revealed);
+        assert_eq!(board.mine_state(&tile(0, 0)), MineState::Flag(1));
+    }
+
+    #[test]
+    fn chord_with_too_many_flags_does_nothing() {
+        let mut board = board_with_mines(3, 3, &[(0, 0), (2, 0)]);
+        board.sweep(&mut NoView, &tile(1, 1)).unwrap();
+        for flag in [tile(0, 0), tile(2, 0), tile(1, 0)] {
+            board.cycle_mine_state(&mut NoView, &flag).unwrap();
+        }
+
+        let outcome = board.chord(&mut NoView, &tile(1, 1)).unwrap();
+
+        assert_eq!(outcome, MoveOutcome::Continue);
+        assert_eq!(board.mine_state(&tile(1, 2)), MineState::Empty);
+    }
+
+    #[test]
+    fn chord_with_misplaced_flags_hits_the_mine() {
+        let mut board = board_with_mines(3, 3, &[(0, 0), (2, 0)]);
+        board.sweep(&mut NoView, &tile(1, 1)).unwrap();
+        board.cycle_mine_state(&mut NoView, &tile(0, 0)).unwrap();
+        board.cycle_mine_state(&mut NoView, &tile(1, 0)).unwrap();
+
+        let outcome = board.chord(&mut NoView, &tile(1, 1)).unwrap();
+
+        assert_eq!(outcome, MoveOutcome::Lost(tile(2, 0)));
+        assert!(board.is_game_over());
+    }
+
+    #[test]
+    fn revealing_every_safe_tile_wins() {
+        // Every safe tile touches a mine, so each sweep reveals just one tile.
+        let corners = [(0, 0), (2, 0), (0, 2), (2, 2)];
+        let mut board = board_with_mines(3, 3, &corners);
+        let safe_tiles: Vec<TileCoordinate> = (0..3)
+            .flat_map(|y| (0..3).map(move |x| (x, y)))
+            .filter(|tile| !corners.contains(tile))
+            .map(|(x, y)| tile(x, y))
+            .collect();
+
+        let (last, rest) = safe_tiles.split_last().unwrap();
+        for tile in rest {
+            assert_eq!(
+                board.sweep(&mut NoView, tile).unwrap(),
+                MoveOutcome::Continue
+            );
+            assert!(!board.is_game_over());
+        }
+        assert_eq!(board.sweep(&mut NoView, last).unwrap(), MoveOutcome::Won);
+        assert!(board.is_game_over());
+    }
+
+    #[test]
+    fn sweeping_a_mine_loses() {
+        let mut board = board_with_mines(3, 3, &[(0, 0), (2, 0)]);
+
+        let outcome = board.sweep(&mut NoView, &tile(2, 0)).unwrap();
+
+        assert_eq!(outcome, MoveOutcome::Lost(tile(2, 0)));
+        assert!(board.is_game_over());
+        // Nothing happens on the board once the game is over.
+        assert_eq!(
+            board.sweep(&mut NoView, &tile(1, 1)).unwrap(),
+            MoveOutcome::Continue
+        );
+        assert_eq!(board.mine_state(&tile(1, 1)), MineState::Empty);
+    }
+
+    #[test]
+    fn mines_are_generated_on_the_first_sweep_away_from_it() {
+        let first = tile(4, 4);
+        for seed in 0..50 {
+            let mut board = Board::new(BoardConfig::BEGINNER, seed);
+            assert!(!board.has_mines());
+
+            let outcome = board.sweep(&mut NoView, &first).unwrap();
+
+            assert!(board.has_mines());
+            assert!(!matches!(outcome, MoveOutcome::Lost(_)));
+            let mines = board.snapshot().mines.unwrap();
+            assert_eq!(mines.iter().sum::<i32>(), BoardConfig::BEGINNER.num_mines());
+            assert_eq!(mines[board.index_helper.compute_index(first.x, first.y)], 0);
+        }
+    }
+
+    #[test]
+    fn safe_opening_keeps_the_neighbors_free() {
+        let first = tile(0, 0);
+        for seed in 0..50 {
+            let mut board = Board::new(BoardConfig::BEGINNER, seed);
+            board.set_first_click_policy(FirstClickPolicy::SafeOpening);
+
+            board.sweep(&mut NoView, &first).unwrap();
+
+            let mines = board.snapshot().mines.unwrap();
+            for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                assert_eq!(mines[board.index_helper.compute_index(x, y)], 0);
+            }
+            // The first tile has no neighboring mines, so it opened up.
+            assert_eq!(board.mine_state(&tile(1, 1)), MineState::Revealed);
+        }
+    }
+
+    #[test]
+    fn snapshot_flags_must_fit_on_a_tile() {
+        let config = BoardConfig::custom(3, 3, 2).unwrap();
+        let mut snapshot = Board::new(config, 0).snapshot();
+        snapshot.mine_states[0] = MineState::Flag(2);
+        assert!(matches!(
+            Board::from_snapshot(snapshot.clone()),
+            Err(SnapshotError::InvalidFlag {
+                max_mines_per_tile: 1,
+                found: 2
+            })
+        ));
+
+        snapshot.max_mines_per_tile = 2;
+        assert!(Board::from_snapshot(snapshot.clone()).is_ok());
+
+        snapshot.mine_states[0] = MineState::Flag(0);
+        assert!(matches!(
+            Board::from_snapshot(snapshot),
+            Err(SnapshotError::InvalidFlag { found: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn last_revealed_covers_the_whole_opening() {
+        let mut board = board_with_mines(3, 3, &[(2, 2)]);
+
+        board.sweep(&mut NoView, &tile(0, 0)).unwrap();
+        let revealed = board.last_revealed();
+        assert_eq!(revealed.len(), 8);
+        assert_eq!(revealed[0], tile(0, 0));
+        assert!(!revealed.contains(&tile(2, 2)));
+
+        // Sweeping a tile that's already open changes nothing.
+        board.sweep(&mut NoView, &tile(1, 1)).unwrap();
+        assert!(board.last_revealed().is_empty());
+    }
+
+    #[test]
+    fn last_revealed_is_empty_after_a_chord_that_does_nothing() {
+        let mut board = board_with_mines(3, 3, &[(0, 0), (2, 0)]);
+        board.sweep(&mut NoView, &tile(1, 1)).unwrap();
+        board.cycle_mine_state(&mut NoView, &tile(0, 0)).unwrap();
+
+        board.chord(&mut NoView, &tile(1, 1)).unwrap();
+        assert!(board.last_revealed().is_empty());
+
+        board.cycle_mine_state(&mut NoView, &tile(2, 0)).unwrap();
+        board.chord(&mut NoView, &tile(1, 1)).unwrap();
+        assert_eq!(board.last_revealed().len(), 6);
+    }
+
+    #[test]
+    fn snapshot_mines_must_fit_around_the_opening() {
+        let config = BoardConfig::custom(9, 9, 80).unwrap();
+        let mut snapshot = Board::new(config, 0).snapshot();
+        assert!(Board::from_snapshot(snapshot.clone()).is_ok());
+
+        snapshot.first_click_policy = FirstClickPolicy::SafeOpening;
+        assert!(matches!(
+            Board::from_snapshot(snapshot.clone()),
+            Err(SnapshotError::NotEnoughRoom(_))
+        ));
+
+        // There's room for them once tiles can hold more than one.
+        snapshot.max_mines_per_tile = 2;
+        assert!(Board::from_snapshot(snapshot).is_ok());
+    }
+
+    // A board where tiles hold up to `max_mines_per_tile` mines, with the given
+    // number of them on each tile.
+    fn board_with_stacked_mines(
+        width: i32,
+        height: i32,
+        max_mines_per_tile: i32,
+        mines: &[((i32, i32), i32)],
+    ) -> Board {
+        let num_mines = mines.iter().map(|(_, count)| count).sum();
+        let config = BoardConfig::custom(width, height, num_mines).unwrap();
+        let index_helper = IndexHelper::new(width, height);
+        let mut layout = vec![0; config.tile_count()];
+        for &((x, y), count) in mines {
+            layout[index_helper.compute_index(x, y)] = count;
+        }
+        let mut snapshot = Board::new(config, 0).snapshot();
+        snapshot.max_mines_per_tile = max_mines_per_tile;
+        Board::from_snapshot(BoardSnapshot {
+            mines: Some(layout),
+            ..snapshot
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn flags_count_up_to_the_most_mines_a_tile_holds() {
+        let mut board = board_with_stacked_mines(3, 3, 3, &[((0, 0), 3)]);
+        let mut states = Vec::new();
+        for _ in 0..5 {
+            board.cycle_mine_state(&mut NoView, &tile(0, 0)).unwrap();
+            states.push(board.mine_state(&tile(0, 0)));
+        }
+        assert_eq!(
+            states,
+            vec![
+                MineState::Flag(1),
+                MineState::Flag(2),
+                MineState::Flag(3),
+                MineState::Question,
+                MineState::Empty
+            ]
+        );
+    }
+
+    #[test]
+    fn chord_counts_the_mines_on_each_flag() {
+        let mut board = board_with_stacked_mines(3, 3, 2, &[((0, 0), 2), ((2, 0), 1)]);
+        board.sweep(&mut NoView, &tile(1, 1)).unwrap();
+        board.cycle_mine_state(&mut NoView, &tile(0, 0)).unwrap();
+        board.cycle_mine_state(&mut NoView, &tile(2, 0)).unwrap();
+
+        // A single flag on the tile with two mines leaves the count short.
+        board.chord(&mut NoView, &tile(1, 1)).unwrap();
+        assert_eq!(board.mine_state(&tile(1, 2)), MineState::Empty);
+
+        board.cycle_mine_state(&mut NoView, &tile(0, 0)).unwrap();
+        assert_eq!(board.mine_state(&tile(0, 0)), MineState::Flag(2));
+        let outcome = board.chord(&mut NoView, &tile(1, 1)).unwrap();
+        assert_eq!(outcome, MoveOutcome::Won);
+        assert_eq!(board.mine_state(&tile(1, 2)), MineState::Revealed);
+    }
+
+    #[test]
+    fn stacked_mines_are_won_by_revealing_every_other_tile() {
+        let mut board = board_with_stacked_mines(4, 1, 3, &[((0, 0), 3)]);
+        assert_eq!(
+            board.sweep(&mut NoView, &tile(1, 0)).unwrap(),
+            MoveOutcome::Continue
+        );
+        assert_eq!(board.neighbor_counts[1], 3);
+        assert_eq!(
+            board.sweep(&mut NoView, &tile(3, 0)).unwrap(),
+            MoveOutcome::Won
+        );
+    }
+
+    #[test]
+    fn the_solver_sees_nothing_on_stacked_mines() {
+        let mut board = board_with_stacked_mines(3, 1, 2, &[((0, 0), 2)]);
+        board.sweep(&mut NoView, &tile(2, 0)).unwrap();
+        assert_eq!(solver::mine_probabilities(&board.visible_board()), None);
+    }
+
+    fn practice_board_with_mines(width: i32, height: i32, mines: &[(i32, i32)]) -> Board {
+        let mut board = board_with_mines(width, height, mines);
+        board.set_practice_mode(true);
+        board
+    }
+
+    #[test]
+    fn undo_takes_back_a_whole_opening() {
+        let wall: Vec<(i32, i32)> = (0..5).map(|y| (2, y)).collect();
+        let mut board = practice_board_with_mines(5, 5, &wall);
+        board.sweep(&mut NoView, &tile(0, 0)).unwrap();
+
+        assert!(board.undo(&mut NoView).unwrap());
+        assert!(board
+            .mine_states
+            .iter()
+            .all(|state| *state == MineState::Empty));
+
+        assert_eq!(
+            board.redo(&mut NoView).unwrap(),
+            Some(MoveOutcome::Continue)
+        );
+        for y in 0..5 {
+            assert_eq!(board.mine_state(&tile(0, y)), MineState::Revealed);
+            assert_eq!(board.mine_state(&tile(1, y)), MineState::Revealed);
+        }
+    }
+
+    #[test]
+    fn undo_takes_back_a_loss() {
+        let mut board = practice_board_with_mines(3, 3, &[(0, 0), (2, 2)]);
+        board.sweep(&mut NoView, &tile(1, 1)).unwrap();
+        assert_eq!(
+            board.sweep(&mut NoView, &tile(0, 0)).unwrap(),
+            MoveOutcome::Lost(tile(0, 0))
+        );
+
+        assert!(board.undo(&mut NoView).unwrap());
+        assert!(!board.is_game_over());
+        assert_eq!(board.mine_state(&tile(0, 0)), MineState::Empty);
+
+        assert_eq!(
+            board.redo(&mut NoView).unwrap(),
+            Some(MoveOutcome::Lost(tile(0, 0)))
+        );
+        assert!(board.is_game_over());
+    }
+
+    #[test]
+    fn undo_takes_back_a_win() {
+        let mut board = practice_board_with_mines(3, 3, &[(0, 0)]);
+        assert_eq!(
+            board.sweep(&mut NoView, &tile(2, 2)).unwrap(),
+            MoveOutcome::Won
+        );
+
+        assert!(board.undo(&mut NoView).unwrap());
+        assert!(!board.is_game_over());
+        assert_eq!(board.redo(&mut NoView).unwrap(), Some(MoveOutcome::Won));
+        assert!(board.is_game_over());
+    }
+
+    #[test]
+    fn undoing_the_first_sweep_takes_back_the_mines() {
+        let mut board = Board::new(BoardConfig::BEGINNER, 7);
+        board.set_practice_mode(true);
+        board.sweep(&mut NoView, &tile(4, 4)).unwrap();
+        assert!(board.has_mines());
+
+        assert!(board.undo(&mut NoView).unwrap());
+        assert!(!board.has_mines());
+        assert!(board.is_untouched());
+
+        // The next sweep places mines of its own, away from wherever it is.
+        board.sweep(&mut NoView, &tile(0, 0)).unwrap();
+        assert!(board.has_mines());
+        assert_eq!(board.mine_state(&tile(0, 0)), MineState::Revealed);
+        assert!(!board.is_game_over());
+    }
+
+    #[test]
+    fn a_new_move_clears_what_could_be_redone() {
+        let mut board = practice_board_with_mines(3, 3, &[(0, 0)]);
+        board.sweep(&mut NoView, &tile(1, 1)).unwrap();
+        board.cycle_mine_state(&mut NoView, &tile(0, 0)).unwrap();
+        assert!(board.undo(&mut NoView).unwrap());
+
+        board.sweep(&mut NoView, &tile(1, 0)).unwrap();
+        assert_eq!(board.redo(&mut NoView).unwrap(), None);
+        assert_eq!(board.mine_state(&tile(0, 0)), MineState::Empty);
+    }
+
+    #[test]
+    fn undo_needs_practice_mode() {
+        let mut board = board_with_mines(3, 3, &[(0, 0)]);
+        board.sweep(&mut NoView, &tile(1, 1)).unwrap();
+
+        assert!(!board.undo(&mut NoView).unwrap());
+        assert_eq!(board.mine_state(&tile(1, 1)), MineState::Revealed);
+        assert_eq!(board.redo(&mut NoView).unwrap(), None);
+    }
+
+    #[test]
+    fn hitting_a_mine_with_lives_left_keeps_the_game_going() {
+        let mut board = board_with_mines(3, 3, &[(0, 0), (2, 2)]);
+        board.set_lives(2);
+
+        assert_eq!(
+            board.sweep(&mut NoView, &tile(0, 0)).unwrap(),
+            MoveOutcome::Continue
+        );
+        assert!(!board.is_game_over());
+        assert_eq!(board.mine_state(&tile(0, 0)), MineState::Revealed);
+        assert_eq!((board.lives_used(), board.lives_left()), (1, 1));
+
+        // The mine that was hit doesn't stand in the way of winning.
+        board.sweep(&mut NoView, &tile(2, 0)).unwrap();
+        assert_eq!(
+            board.sweep(&mut NoView, &tile(0, 2)).unwrap(),
+            MoveOutcome::Won
+        );
+
+        let clicks = ClickCounts {
+            left: 3,
+            right: 0,
+            chord: 0,
+        };
+        let stats = board
+            .game_stats(MoveOutcome::Won, Duration::from_secs(5), clicks)
+            .unwrap();
+        assert!(stats.won);
+        assert_eq!(stats.lives_used, 1);
+        assert_eq!(stats.clicks, clicks);
+    }
+
+    #[test]
+    fn the_last_life_ends_the_game() {
+        let mut board = board_with_mines(3, 3, &[(0, 0), (2, 2)]);
+        board.set_lives(2);
+        board.sweep(&mut NoView, &tile(0, 0)).unwrap();
+        board.sweep(&mut NoView, &tile(1, 1)).unwrap();
+
+        let outcome = board.sweep(&mut NoView, &tile(2, 2)).unwrap();
+        assert_eq!(outcome, MoveOutcome::Lost(tile(2, 2)));
+        assert!(board.is_game_over());
+        assert_eq!((board.lives_used(), board.lives_left()), (2, 0));
+
+        let stats = board
+            .game_stats(outcome, Duration::from_secs(5), ClickCounts::default())
+            .unwrap();
+        assert!(!stats.won);
+        // The mine that ended the game counts as a life used too.
+        assert_eq!(stats.lives_used, 2);
+    }
+
+    #[test]
+    fn there_are_no_stats_before_the_mines_are_placed() {
+        let board = Board::new(BoardConfig::custom(3, 3, 1).unwrap(), 0);
+        assert_eq!(
+            board.game_stats(
+                MoveOutcome::Continue,
+                Duration::ZERO,
+                ClickCounts::default()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn snapshots_with_impossible_rules_are_refused() {
+        let snapshot = board_with_mines(3, 3, &[(0, 0)]).snapshot();
+
+        for max_mines_per_tile in [0, BoardConfig::MAX_MINES_PER_TILE + 1] {
+            assert_eq!(
+                Board::from_snapshot(BoardSnapshot {
+                    max_mines_per_tile,
+                    ..snapshot.clone()
+                })
+                .err(),
+                Some(SnapshotError::InvalidMaxMinesPerTile(max_mines_per_tile))
+            );
+        }
+        assert_eq!(
+            Board::from_snapshot(BoardSnapshot {
+                lives: 0,
+                ..snapshot
+            })
+            .err(),
+            Some(SnapshotError::InvalidLives(0))
+        );
+    }
+
+    // Sweeps the middle of a 16x16 board with 40 mines, generated the given way.
+    fn sweep_middle(seed: u64, generation_mode: GenerationMode) -> Board {
+        let mut board = Board::new(BoardConfig::custom(16, 16, 40).unwrap(), seed);
+        board.set_generation_mode(generation_mode);
+        board.sweep(&mut NoView, &tile(8, 8)).unwrap();
+        board
+    }
+
+    fn can_clear_from_the_middle(board: &Board) -> bool {
+        solver::can_clear_without_guessing(
+            VisibleBoard::new(16, 16, 40),
+            &board.mines,
+            &board.neighbor_counts,
+            &tile(8, 8),
+        )
+    }
+
+    #[test]
+    fn no_guess_boards_can_be_cleared_from_the_first_click() {
+        for seed in 0..10 {
+            let board = sweep_middle(seed, GenerationMode::NoGuess { max_attempts: 1000 });
+            assert!(can_clear_from_the_middle(&board), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn no_guess_falls_back_to_the_last_layout() {
+        // A seed whose first layout needs a guess.
+        let seed = (0..100)
+            .find(|seed| !can_clear_from_the_middle(&sweep_middle(*seed, GenerationMode::Random)))
+            .unwrap();
+        let random = sweep_middle(seed, GenerationMode::Random);
+
+        // With a single attempt that layout is all there is, and it's used
+        // anyway. Asking for no attempts still makes one.
+        for max_attempts in [0, 1] {
+            let board = sweep_middle(seed, GenerationMode::NoGuess { max_attempts });
+            assert_eq!(board.mines, random.mines);
+            assert!(!board.is_game_over());
+        }
+
+        // More attempts move on from it.
+        let board = sweep_middle(seed, GenerationMode::NoGuess { max_attempts: 1000 });
+        assert_ne!(board.mines, random.mines);
+        assert!(can_clear_from_the_middle(&board));
+    }
+
+    #[test]
+    fn a_seed_always_makes_the_same_board() {
+        let board = sweep_middle(1234, GenerationMode::Random);
+        assert_eq!(
+            sweep_middle(1234, GenerationMode::Random).mines,
+            board.mines
+        );
+
+        // Starting over with the seed brings it back too.
+        let mut reset = sweep_middle(99, GenerationMode::Random);
+        reset.reset(board.config(), 1234);
+        reset.sweep(&mut NoView, &tile(8, 8)).unwrap();
+        assert_eq!(reset.mines, board.mines);
+
+        assert_ne!(
+            sweep_middle(1235, GenerationMode::Random).mines,
+            board.mines
+        );
+    }
+
+    #[test]
+    fn preset_mines_keep_the_rules_of_the_game() {
+        let mut board = Board::new(BoardConfig::custom(3, 3, 2).unwrap(), 0);
+        board.set_lives(2);
+        board.set_practice_mode(true);
+        board.preset_mines(vec![1, 0, 0, 0, 0, 0, 0, 0, 1]).unwrap();
+
+        assert!(board.has_mines());
+        assert_eq!(board.lives(), 2);
+        assert!(board.is_practice_mode());
+        // The first sweep plays on the preset mines.
+        assert_eq!(
+            board.sweep(&mut NoView, &tile(0, 0)).unwrap(),
+            MoveOutcome::Continue
+        );
+        assert_eq!(board.lives_used(), 1);
+
+        assert_eq!(
+            board.preset_mines(vec![1; 9]).err(),
+            Some(SnapshotError::MineCountMismatch {
+                expected: 2,
+                found: 9
+            })
+        );
+    }
+
+    // A board on the given topology with single mines on the given tiles.
+    fn board_with_topology(
+        width: i32,
+        height: i32,
+        topology: Topology,
+        mines: &[(i32, i32)],
+    ) -> Board {
+        Board::from_snapshot(BoardSnapshot {
+            topology,
+            ..board_with_mines(width, height, mines).snapshot()
+        })
+        .unwrap()
+    }
+
+    // The rings of a spiral, each sorted so that only which tiles are in
+    // them matters.
+    fn rings(
+        board: &Board,
+        (indices, tiles_per_ring): (VecDeque<usize>, VecDeque<i32>),
+    ) -> Vec<Vec<(i32, i32)>> {
+        let mut indices = indices.into_iter();
+        tiles_per_ring
+            .into_iter()
+            .map(|count| {
+                let mut ring: Vec<(i32, i32)> = indices
+                    .by_ref()
+                    .take(count as usize)
+                    .map(|index| {
+                        let tile = board.coordinate(index);
+                        (tile.x, tile.y)
+                    })
+                    .collect();
+                ring.sort();
+                ring
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hidden_mines_leave_out_the_ones_that_were_hit() {
+        let mut board = board_with_mines(3, 3, &[(0, 0), (2, 2)]);
+        board.set_lives(2);
+        board.sweep(&mut NoView, &tile(0, 0)).unwrap();
+        board.sweep(&mut NoView, &tile(2, 0)).unwrap();
+        assert_eq!(
+            board.sweep(&mut NoView, &tile(0, 2)).unwrap(),
+            MoveOutcome::Won
+        );
+
+        assert_eq!(board.hidden_mines(), vec![(tile(2, 2), 1)]);
+    }
+
+    #[test]
+    fn hidden_mines_count_the_mines_on_each_tile() {
+        let board = board_with_stacked_mines(5, 1, 3, &[((1, 0), 3), ((3, 0), 1)]);
+        assert_eq!(board.hidden_mines(), vec![(tile(1, 0), 3), (tile(3, 0), 1)]);
+    }
+
+    #[test]
+    fn spiral_follows_hex_rows() {
+        let hex = Topology::new(TileShape::Hex, false);
+        let board = board_with_topology(3, 3, hex, &[(0, 0)]);
+
+        // (1, 1) is on an odd row, which is shifted right, so the tiles on
+        // the left of the rows above and below it are two steps away.
+        assert_eq!(
+            rings(&board, board.tiles_in_spiral_order(&tile(1, 1))),
+            vec![
+                vec![(1, 1)],
+                vec![(0, 1), (1, 0), (1, 2), (2, 0), (2, 1), (2, 2)],
+                vec![(0, 0), (0, 2)],
+            ]
+        );
+    }
+
+    #[test]
+    fn spiral_wraps_around_the_edges() {
+        let wrapping = Topology::new(TileShape::Square, true);
+        let board = board_with_topology(4, 4, wrapping, &[(0, 0)]);
+
+        let spiral = rings(&board, board.tiles_in_spiral_order(&tile(0, 0)));
+        assert_eq!(spiral.len(), 3);
+        assert_eq!(
+            spiral[1],
+            vec![
+                (0, 1),
+                (0, 3),
+                (1, 0),
+                (1, 1),
+                (1, 3),
+                (3, 0),
+                (3, 1),
+                (3, 3)
+            ]
+        );
+        // Everything in the middle row and column is two steps away.
+        assert_eq!(spiral[2].len(), 7);
+
+        let flat = board_with_mines(4, 4, &[(0, 0)]);
+        let (_, tiles_per_ring) = flat.tiles_in_spiral_order(&tile(0, 0));
+        assert_eq!(tiles_per_ring, [1, 3, 5, 7]);
+    }
+
+    #[test]
+    fn loss_reveal_shows_what_each_mine_and_flag_was() {
+        let mut board = board_with_mines(3, 3, &[(0, 0), (2, 0), (2, 2)]);
+        board.cycle_mine_state(&mut NoView, &tile(0, 0)).unwrap();
+        board.cycle_mine_state(&mut NoView, &tile(1, 2)).unwrap();
+        assert_eq!(
+            board.sweep(&mut NoView, &tile(2, 2)).unwrap(),
+            MoveOutcome::Lost(tile(2, 2))
+        );
+
+        // One tile at a time, in board index order.
+        assert_eq!(
+            board.loss_reveal(&tile(2, 2)),
+            vec![
+                (tile(0, 0), LossReveal::CorrectFlag),
+                (tile(1, 2), LossReveal::WrongFlag),
+                (tile(2, 0), LossReveal::Mine),
+                (tile(2, 2), LossReveal::FatalMine),
+            ]
+        );
+    }
+
+    #[test]
+    fn loss_reveal_checks_how_many_mines_a_flag_is_on() {
+        let mut board = board_with_stacked_mines(4, 1, 2, &[((0, 0), 2), ((3, 0), 1)]);
+        board.cycle_mine_state(&mut NoView, &tile(0, 0)).unwrap();
+        board.sweep(&mut NoView, &tile(3, 0)).unwrap();
+
+        assert_eq!(
+            board.loss_reveal(&tile(3, 0)),
+            vec![
+                (tile(0, 0), LossReveal::WrongFlag),
+                (tile(3, 0), LossReveal::FatalMine),
+            ]
+        );
+    }
+
+    #[test]
+    fn mines_spiral_out_from_the_fatal_mine() {
+        let hex = Topology::new(TileShape::Hex, false);
+        let board = board_with_topology(3, 3, hex, &[(1, 1), (2, 0), (0, 0)]);
+        assert_eq!(
+            rings(&board, board.mines_in_spiral_order(&tile(1, 1))),
+            vec![vec![(1, 1)], vec![(2, 0)], vec![(0, 0)]]
+        );
+
+        // Across the corner of a wrapping board, (3, 3) is right next to
+        // (0, 0), while it's the farthest tile on a flat one.
+        let mines = [(0, 0), (2, 2), (3, 3)];
+        let wrapping = Topology::new(TileShape::Square, true);
+        let board = board_with_topology(4, 4, wrapping, &mines);
+        assert_eq!(
+            rings(&board, board.mines_in_spiral_order(&tile(0, 0))),
+            vec![vec![(0, 0)], vec![(3, 3)], vec![(2, 2)]]
+        );
+        let flat = board_with_mines(4, 4, &mines);
+        assert_eq!(
+            rings(&flat, flat.mines_in_spiral_order(&tile(0, 0))),
+            vec![vec![(0, 0)], vec![(2, 2)], vec![(3, 3)]]
+        );
+    }
+}
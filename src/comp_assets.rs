@@ -1,4 +1,4 @@
-use crate::minesweeper::MineState;
+use minesweeper_rs::{board::MineState, topology::Neighborhood};
 use std::collections::HashMap;
 use windows::{
     core::{Interface, Result},
@@ -24,8 +24,38 @@ fn get_dot_shape(
     Ok(shape)
 }
 
+// An "X" across the whole tile.
+fn get_cross_shape(
+    compositor: &Compositor,
+    tile_size: &Vector2,
+    brush: &CompositionColorBrush,
+) -> Result<CompositionShape> {
+    let length = tile_size.X.min(tile_size.Y) * 0.8;
+    let thickness = length / 8.0;
+    let geometry = compositor.CreateRectangleGeometry()?;
+    geometry.SetSize(Vector2::new(length, thickness))?;
+    let geometry: CompositionGeometry = geometry.cast()?;
+
+    let container_shape = compositor.CreateContainerShape()?;
+    let shapes = container_shape.Shapes()?;
+    for angle in [45.0, -45.0] {
+        let shape = compositor.CreateSpriteShapeWithGeometry(&geometry)?;
+        shape.SetFillBrush(brush)?;
+        shape.SetOffset(Vector2::new(
+            (tile_size.X - length) / 2.0,
+            (tile_size.Y - thickness) / 2.0,
+        ))?;
+        shape.SetCenterPoint(Vector2::new(length / 2.0, thickness / 2.0))?;
+        shape.SetRotationAngleInDegrees(angle)?;
+        shapes.Append(&shape)?;
+    }
+    container_shape.cast()
+}
+
 pub struct CompAssets {
     mine_brush: CompositionColorBrush,
+    fatal_mine_brush: CompositionColorBrush,
+    wrong_flag_shape: CompositionShape,
     mine_state_brushes: HashMap<MineState, CompositionColorBrush>,
     mine_count_background_brushes: HashMap<i32, CompositionColorBrush>,
     mine_count_shapes: HashMap<i32, CompositionShape>,
@@ -34,9 +64,14 @@ pub struct CompAssets {
 impl CompAssets {
     pub fn new(compositor: &Compositor, tile_size: &Vector2) -> Result<Self> {
         let mine_brush = compositor.CreateColorBrushWithColor(Colors::Red()?)?;
+        let fatal_mine_brush = compositor.CreateColorBrushWithColor(Colors::DarkRed()?)?;
+        let cross_brush = compositor.CreateColorBrushWithColor(Colors::Black()?)?;
+        let wrong_flag_shape = get_cross_shape(compositor, tile_size, &cross_brush)?;
 
         let mut result = Self {
             mine_brush,
+            fatal_mine_brush,
+            wrong_flag_shape,
             mine_state_brushes: HashMap::new(),
             mine_count_background_brushes: HashMap::new(),
             mine_count_shapes: HashMap::new(),
@@ -51,17 +86,39 @@ impl CompAssets {
         self.mine_brush.clone()
     }
 
+    /// The mine that ended the game.
+    pub fn get_fatal_mine_brush(&self) -> CompositionColorBrush {
+        self.fatal_mine_brush.clone()
+    }
+
+    /// Drawn over a flag that turned out to be wrong.
+    pub fn get_wrong_flag_shape(&self) -> CompositionShape {
+        self.wrong_flag_shape.clone()
+    }
+
     pub fn get_shape_from_mine_count(&self, count: i32) -> CompositionShape {
-        self.mine_count_shapes.get(&count).unwrap().clone()
+        // Tiles holding several mines can push a count past the largest
+        // neighborhood, those counts all share the fullest shape.
+        self.mine_count_shapes
+            .get(&count.min(Neighborhood::MAX_OFFSETS as i32))
+            .unwrap()
+            .clone()
     }
 
     pub fn get_color_brush_from_mine_state(&self, state: MineState) -> CompositionColorBrush {
+        // Flags look the same however many mines they're on, the count is
+        // drawn on top of them.
+        let state = match state {
+            MineState::Flag(_) => MineState::Flag(1),
+            state => state,
+        };
         self.mine_state_brushes.get(&state).unwrap().clone()
     }
 
     pub fn get_color_brush_from_mine_count(&self, count: i32) -> CompositionColorBrush {
+        // Only custom neighborhoods go past 8, those counts all share a color.
         self.mine_count_background_brushes
-            .get(&count)
+            .get(&count.min(9))
             .unwrap()
             .clone()
     }
@@ -73,7 +130,7 @@ impl CompAssets {
             compositor.CreateColorBrushWithColor(Colors::Blue()?)?,
         );
         self.mine_state_brushes.insert(
-            MineState::Flag,
+            MineState::Flag(1),
             compositor.CreateColorBrushWithColor(Colors::Orange()?)?,
         );
         self.mine_state_brushes.insert(
@@ -112,6 +169,8 @@ impl CompAssets {
             8,
             compositor.CreateColorBrushWithColor(Colors::DarkSeaGreen()?)?,
         );
+        self.mine_count_background_brushes
+            .insert(9, compositor.CreateColorBrushWithColor(Colors::Khaki()?)?);
         self.mine_count_background_brushes.insert(
             0,
             compositor.CreateColorBrushWithColor(Colors::WhiteSmoke()?)?,
@@ -232,6 +291,31 @@ impl CompAssets {
             append_shape(&shapes, Vector2::new(half_x, third_y * 2.0))?;
             self.mine_count_shapes.insert(8, container_shape.cast()?);
         }
+        // 9 and up, which only custom neighborhoods can reach. These are
+        // smaller dots in rows that fill up from the top.
+        for count in 9..=Neighborhood::MAX_OFFSETS as i32 {
+            let columns = (count as f32).sqrt().ceil();
+            let rows = (count as f32 / columns).ceil();
+            let spacing = Vector2::new(tile_size.X / (columns + 1.0), tile_size.Y / (rows + 1.0));
+            let small_circle_geometry = compositor.CreateEllipseGeometry()?;
+            small_circle_geometry.SetRadius(spacing / 4.0)?;
+            let small_circle_geometry: CompositionGeometry = small_circle_geometry.cast()?;
+
+            let container_shape = compositor.CreateContainerShape()?;
+            let shapes = container_shape.Shapes()?;
+            for dot in 0..count {
+                let column = (dot % columns as i32) as f32 + 1.0;
+                let row = (dot / columns as i32) as f32 + 1.0;
+                shapes.Append(&get_dot_shape(
+                    compositor,
+                    &small_circle_geometry,
+                    &dot_brush,
+                    Vector2::new(spacing.X * column, spacing.Y * row),
+                )?)?;
+            }
+            self.mine_count_shapes
+                .insert(count, container_shape.cast()?);
+        }
 
         Ok(())
     }
@@ -1,17 +1,24 @@
 use crate::comp_assets::CompAssets;
-use crate::minesweeper::{IndexHelper, MineState};
-use crate::visual_grid::{TileCoordinate, VisualGrid};
+use crate::numerics::ToPoint;
+use crate::visual_grid::VisualGrid;
+use minesweeper_rs::board::{
+    GameView, IndexHelper, LossReveal, MineGenerationError, MineState, TileCoordinate,
+};
+use minesweeper_rs::topology::Topology;
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 use windows::{
-    core::{h, Result},
-    Foundation::TimeSpan,
+    core::{h, Error, Result},
+    Foundation::{TimeSpan, TypedEventHandler},
     Graphics::SizeInt32,
+    Win32::Foundation::E_INVALIDARG,
     UI::{
         Colors,
         Composition::{
-            AnimationIterationBehavior, CompositionBatchTypes, CompositionBorderMode, Compositor,
-            ContainerVisual, SpriteVisual,
+            AnimationIterationBehavior, CompositionBatchTypes, CompositionBorderMode,
+            CompositionShape, Compositor, ContainerVisual, SpriteVisual,
         },
     },
 };
@@ -27,7 +34,8 @@ pub struct CompUI {
     game_board: VisualGrid,
     assets: CompAssets,
 
-    mine_animation_playing: bool,
+    // Shared with the handler that clears it once the animation completes.
+    animation_playing: Arc<AtomicBool>,
 }
 
 impl CompUI {
@@ -72,18 +80,16 @@ impl CompUI {
 
             game_board,
             assets,
-            mine_animation_playing: false,
+            animation_playing: Arc::new(AtomicBool::new(false)),
         })
     }
 
-    pub fn hit_test(&self, point: &Vector2) -> Result<Option<TileCoordinate>> {
-        let window_size = &self.parent_size;
-        let scale = self.compute_scale_factor()?;
-        let real_board_size = self.game_board.size()? * scale;
-        let real_offset = (window_size - real_board_size) / 2.0;
-
-        let point = (point - real_offset) / scale;
-        Ok(self.game_board.hit_test(&point))
+    pub fn hit_test(&self, point: &Vector2) -> Option<TileCoordinate> {
+        self.game_board.layout().hit_test_in_window(
+            point.to_point(),
+            self.parent_size.to_point(),
+            self.game_board_margin.to_point(),
+        )
     }
 
     pub fn resize(&mut self, new_size: &Vector2) -> Result<()> {
@@ -100,22 +106,8 @@ impl CompUI {
         self.game_board.current_selected_tile()
     }
 
-    pub fn update_tile_with_state(
-        &self,
-        tile_coordinate: &TileCoordinate,
-        mine_state: MineState,
-    ) -> Result<()> {
-        let visual = self
-            .game_board
-            .get_tile(tile_coordinate.x, tile_coordinate.y)
-            .unwrap();
-
-        visual.SetBrush(&self.assets.get_color_brush_from_mine_state(mine_state))?;
-        Ok(())
-    }
-
-    pub fn reset(&mut self, grid_size_in_tiles: &SizeInt32) -> Result<()> {
-        self.game_board.reset(grid_size_in_tiles)?;
+    pub fn reset(&mut self, grid_size_in_tiles: &SizeInt32, topology: Topology) -> Result<()> {
+        self.game_board.reset(grid_size_in_tiles, topology)?;
         self.index_helper = IndexHelper::new(grid_size_in_tiles.Width, grid_size_in_tiles.Height);
 
         for visual in self.game_board.tiles_iter() {
@@ -127,109 +119,154 @@ impl CompUI {
         }
 
         self.update_board_scale(&self.parent_size.clone())?;
-        self.mine_animation_playing = false;
+        self.animation_playing.store(false, Ordering::SeqCst);
 
         Ok(())
     }
 
-    pub fn update_tile_as_mine(&self, tile_coordinate: &TileCoordinate) -> Result<()> {
+    /// Shows what a mine or flagged tile turned out to be once the game is
+    /// lost. Correct flags stay as they are.
+    pub fn show_loss_reveal(
+        &mut self,
+        tile_coordinate: &TileCoordinate,
+        reveal: LossReveal,
+    ) -> Result<()> {
         let visual = self
             .game_board
             .get_tile(tile_coordinate.x, tile_coordinate.y)
             .unwrap();
 
-        visual.SetBrush(&self.assets.get_mine_brush())?;
+        match reveal {
+            LossReveal::FatalMine => {
+                visual.Children()?.RemoveAll()?;
+                visual.SetBrush(&self.assets.get_fatal_mine_brush())?;
+            }
+            LossReveal::Mine => {
+                visual.Children()?.RemoveAll()?;
+                visual.SetBrush(&self.assets.get_mine_brush())?;
+            }
+            LossReveal::CorrectFlag => {}
+            LossReveal::WrongFlag => {
+                self.show_shape(visual, &self.assets.get_wrong_flag_shape())?
+            }
+        }
         Ok(())
     }
 
-    pub fn update_tile_with_mine_count(
-        &self,
-        tile_coordinate: &TileCoordinate,
-        num_mines: i32,
+    /// Plays the animation on every mine, ring by ring. `on_completed` is
+    /// called once it's over, unless it was stopped or the board was reset
+    /// first.
+    pub fn play_mine_animations(
+        &mut self,
+        mine_indices: VecDeque<usize>,
+        mines_per_ring: VecDeque<i32>,
+        on_completed: impl Fn() + Send + 'static,
     ) -> Result<()> {
-        let visual = self
-            .game_board
-            .get_tile(tile_coordinate.x, tile_coordinate.y)
-            .unwrap();
-        visual.SetBrush(&self.assets.get_color_brush_from_mine_count(num_mines))?;
+        self.play_animations_in_rings(
+            mine_indices,
+            mines_per_ring,
+            Duration::from_millis(100),
+            Self::play_mine_animation,
+            on_completed,
+        )
+    }
 
-        if num_mines > 0 {
-            let shape = self.assets.get_shape_from_mine_count(num_mines);
-            let shape_visual = self.compositor.CreateShapeVisual()?;
-            shape_visual.SetRelativeSizeAdjustment(Vector2::new(1.0, 1.0))?;
-            shape_visual.Shapes()?.Append(&shape)?;
-            shape_visual.SetBorderMode(CompositionBorderMode::Soft)?;
-            visual.Children()?.InsertAtTop(&shape_visual)?;
+    /// Sends a wave over the tiles, ring by ring. `on_completed` is called
+    /// the same way as for `play_mine_animations`.
+    pub fn play_win_animations(
+        &mut self,
+        tile_indices: VecDeque<usize>,
+        tiles_per_ring: VecDeque<i32>,
+        on_completed: impl Fn() + Send + 'static,
+    ) -> Result<()> {
+        self.play_animations_in_rings(
+            tile_indices,
+            tiles_per_ring,
+            Duration::from_millis(40),
+            Self::play_win_animation,
+            on_completed,
+        )
+    }
+
+    pub fn stop_animations(&mut self) -> Result<()> {
+        for visual in self.game_board.tiles_iter() {
+            visual.StopAnimation(h!("Scale"))?;
+            visual.SetScale(Vector3::new(1.0, 1.0, 1.0))?;
         }
+        self.animation_playing.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn is_animation_playing(&self) -> bool {
+        self.animation_playing.load(Ordering::SeqCst)
+    }
+
+    fn update_board_scale(&mut self, window_size: &Vector2) -> Result<()> {
+        let scale_factor = self
+            .game_board
+            .layout()
+            .scale_to_fit(window_size.to_point(), self.game_board_margin.to_point());
+        self.game_board
+            .root()
+            .SetScale(Vector3::new(scale_factor, scale_factor, 1.0))?;
+        Ok(())
+    }
 
+    fn show_count(&self, visual: &SpriteVisual, count: i32) -> Result<()> {
+        self.show_shape(visual, &self.assets.get_shape_from_mine_count(count))
+    }
+
+    fn show_shape(&self, visual: &SpriteVisual, shape: &CompositionShape) -> Result<()> {
+        let shape_visual = self.compositor.CreateShapeVisual()?;
+        shape_visual.SetRelativeSizeAdjustment(Vector2::new(1.0, 1.0))?;
+        shape_visual.Shapes()?.Append(shape)?;
+        shape_visual.SetBorderMode(CompositionBorderMode::Soft)?;
+        visual.Children()?.InsertAtTop(&shape_visual)?;
         Ok(())
     }
 
-    pub fn play_mine_animations(
+    // Starts the animation on each ring of tiles one step after the ring
+    // before it.
+    fn play_animations_in_rings(
         &mut self,
-        mut mine_indices: VecDeque<usize>,
-        mut mines_per_ring: VecDeque<i32>,
+        mut indices: VecDeque<usize>,
+        mut tiles_per_ring: VecDeque<i32>,
+        animation_delay_step: Duration,
+        play_animation: fn(&Self, usize, &TimeSpan) -> Result<()>,
+        on_completed: impl Fn() + Send + 'static,
     ) -> Result<()> {
         // Create an animation batch so that we can know when the animations complete
         let batch = self
             .compositor
             .CreateScopedBatch(CompositionBatchTypes::Animation)?;
 
-        let animation_delay_step = Duration::from_millis(100);
         let mut current_delay = Duration::from_millis(0);
-        let mut current_mines_count = 0;
-        while !mine_indices.is_empty() {
-            let mine_index = *mine_indices.front().unwrap();
-            self.play_mine_animation(mine_index, &TimeSpan::from(current_delay))?;
-            current_mines_count += 1;
-
-            let mines_on_current_level = *mines_per_ring.front().unwrap();
-            if current_mines_count == mines_on_current_level {
-                current_mines_count = 0;
-                mines_per_ring.pop_front().unwrap();
+        let mut current_tiles_count = 0;
+        while !indices.is_empty() {
+            let index = *indices.front().unwrap();
+            play_animation(self, index, &TimeSpan::from(current_delay))?;
+            current_tiles_count += 1;
+
+            let tiles_on_current_level = *tiles_per_ring.front().unwrap();
+            if current_tiles_count == tiles_on_current_level {
+                current_tiles_count = 0;
+                tiles_per_ring.pop_front().unwrap();
                 current_delay += animation_delay_step;
             }
-            mine_indices.pop_front().unwrap();
+            indices.pop_front().unwrap();
         }
 
         // Subscribe to the completion event and complete the batch
-        // TODO: events
+        self.animation_playing.store(true, Ordering::SeqCst);
+        let animation_playing = self.animation_playing.clone();
+        batch.Completed(&TypedEventHandler::new(move |_, _| {
+            if animation_playing.swap(false, Ordering::SeqCst) {
+                on_completed();
+            }
+            Ok(())
+        }))?;
         batch.End()?;
 
-        self.mine_animation_playing = true;
-
-        Ok(())
-    }
-
-    pub fn is_animation_playing(&self) -> bool {
-        self.mine_animation_playing
-    }
-
-    fn compute_scale_factor_from_size(&self, window_size: &Vector2) -> Result<f32> {
-        let board_size = self.game_board.size()?;
-        let board_size = board_size + self.game_board_margin;
-
-        let window_ratio = window_size.X / window_size.Y;
-        let board_ratio = board_size.X / board_size.Y;
-
-        let scale_factor = if window_ratio > board_ratio {
-            window_size.Y / board_size.Y
-        } else {
-            window_size.X / board_size.X
-        };
-
-        Ok(scale_factor)
-    }
-
-    fn compute_scale_factor(&self) -> Result<f32> {
-        self.compute_scale_factor_from_size(&self.parent_size)
-    }
-
-    fn update_board_scale(&mut self, window_size: &Vector2) -> Result<()> {
-        let scale_factor = self.compute_scale_factor_from_size(window_size)?;
-        self.game_board
-            .root()
-            .SetScale(Vector3::new(scale_factor, scale_factor, 1.0))?;
         Ok(())
     }
 
@@ -245,8 +282,6 @@ impl CompUI {
         let parent_children = visual.Parent()?.Children()?;
         parent_children.Remove(visual)?;
         parent_children.InsertAtTop(visual)?;
-        // Make sure the visual has the mine brush
-        visual.SetBrush(&self.assets.get_mine_brush())?;
         // Play the animation
         let animation = self.compositor.CreateVector3KeyFrameAnimation()?;
         animation.InsertKeyFrame(0.0, Vector3::new(1.0, 1.0, 1.0))?;
@@ -259,4 +294,114 @@ impl CompUI {
         visual.StartAnimation(h!("Scale"), &animation)?;
         Ok(())
     }
+
+    fn play_win_animation(&self, index: usize, delay: &TimeSpan) -> Result<()> {
+        let visual = self
+            .game_board
+            .get_tile(
+                self.index_helper.compute_x_from_index(index),
+                self.index_helper.compute_y_from_index(index),
+            )
+            .unwrap();
+        // The tiles shrink rather than grow, so that they don't overlap their
+        // neighbors on the way.
+        let animation = self.compositor.CreateVector3KeyFrameAnimation()?;
+        animation.InsertKeyFrame(0.0, Vector3::new(1.0, 1.0, 1.0))?;
+        animation.InsertKeyFrame(0.5, Vector3::new(0.6, 0.6, 1.0))?;
+        animation.InsertKeyFrame(1.0, Vector3::new(1.0, 1.0, 1.0))?;
+        animation.SetDuration(TimeSpan::from(Duration::from_millis(400)))?;
+        animation.SetDelayTime(*delay)?;
+        animation.SetIterationBehavior(AnimationIterationBehavior::Count)?;
+        animation.SetIterationCount(1)?;
+        visual.StartAnimation(h!("Scale"), &animation)?;
+        Ok(())
+    }
+}
+
+/// The errors the board can hand back through [`CompUI`]: either drawing
+/// failed or the board couldn't generate its mines.
+#[derive(Debug)]
+pub enum ViewError {
+    Windows(Error),
+    Generation(MineGenerationError),
+}
+
+impl From<Error> for ViewError {
+    fn from(error: Error) -> Self {
+        ViewError::Windows(error)
+    }
+}
+
+impl From<MineGenerationError> for ViewError {
+    fn from(error: MineGenerationError) -> Self {
+        ViewError::Generation(error)
+    }
+}
+
+impl From<ViewError> for Error {
+    fn from(error: ViewError) -> Self {
+        match error {
+            ViewError::Windows(error) => error,
+            ViewError::Generation(error) => Error::new(E_INVALIDARG, error.to_string()),
+        }
+    }
+}
+
+impl GameView for CompUI {
+    type Error = ViewError;
+
+    fn update_tile_with_state(
+        &mut self,
+        tile_coordinate: &TileCoordinate,
+        mine_state: MineState,
+    ) -> std::result::Result<(), ViewError> {
+        let visual = self
+            .game_board
+            .get_tile(tile_coordinate.x, tile_coordinate.y)
+            .unwrap();
+
+        // The tile may be going back to an unrevealed state after an undo.
+        visual.Children()?.RemoveAll()?;
+        visual.SetBrush(&self.assets.get_color_brush_from_mine_state(mine_state))?;
+
+        // A flag on more than one mine shows how many.
+        if let MineState::Flag(mines) = mine_state {
+            if mines > 1 {
+                self.show_count(visual, mines)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn update_tile_as_mine(
+        &mut self,
+        tile_coordinate: &TileCoordinate,
+    ) -> std::result::Result<(), ViewError> {
+        let visual = self
+            .game_board
+            .get_tile(tile_coordinate.x, tile_coordinate.y)
+            .unwrap();
+
+        visual.SetBrush(&self.assets.get_mine_brush())?;
+        Ok(())
+    }
+
+    fn update_tile_with_mine_count(
+        &mut self,
+        tile_coordinate: &TileCoordinate,
+        num_mines: i32,
+    ) -> std::result::Result<(), ViewError> {
+        let visual = self
+            .game_board
+            .get_tile(tile_coordinate.x, tile_coordinate.y)
+            .unwrap();
+        visual.Children()?.RemoveAll()?;
+        visual.SetBrush(&self.assets.get_color_brush_from_mine_count(num_mines))?;
+
+        if num_mines > 0 {
+            self.show_count(visual, num_mines)?;
+        }
+
+        Ok(())
+    }
 }
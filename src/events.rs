@@ -0,0 +1,210 @@
+use crate::board::{Board, BoardConfig, MineState, TileCoordinate};
+use crate::stats::GameStats;
+
+/// Something that happened in a game, for anything that wants to follow along
+/// without being part of the rules.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameEvent {
+    /// A board was set up to be played, either empty for a new game or
+    /// restored from a saved one.
+    GameStarted { config: BoardConfig, seed: u64 },
+    /// The mines were placed, either by the first sweep or up front for a
+    /// board that was made elsewhere.
+    MinesGenerated,
+    /// A sweep or chord uncovered a tile. Sent for every tile that was
+    /// uncovered, including the ones around a tile with no neighboring mines
+    /// and a mine that was hit.
+    TileRevealed { tile: TileCoordinate },
+    /// The player changed the mark on an unrevealed tile. Cycling a mark that
+    /// ends up where it started isn't sent.
+    TileMarked {
+        tile: TileCoordinate,
+        state: MineState,
+    },
+    /// The player chorded on a tile and it uncovered the tiles around it.
+    /// Comes before their `TileRevealed` events. Chords that don't change
+    /// anything aren't sent.
+    ChordPerformed { tile: TileCoordinate },
+    /// Undo in practice mode put a tile back to `state`, either covering it up
+    /// again or changing its mark back. Sent for every tile the move changed,
+    /// in the reverse order. Undoing the first sweep also takes back the
+    /// mines, and the next sweep sends `MinesGenerated` again. Redo sends the
+    /// move's `MinesGenerated`, `TileRevealed` and `TileMarked` events again.
+    TileUndone {
+        tile: TileCoordinate,
+        state: MineState,
+    },
+    /// The stats are `None` if the game ended without any mines placed.
+    /// Redoing the winning move in practice mode doesn't send it again.
+    GameWon { stats: Option<GameStats> },
+    /// `tile` is the mine that ended the game. Redoing the losing move in
+    /// practice mode doesn't send it again.
+    GameLost {
+        tile: TileCoordinate,
+        stats: Option<GameStats>,
+    },
+    /// The animation at the end of a game finished, so the board takes input
+    /// again.
+    AnimationCompleted,
+}
+
+type Listener = Box<dyn FnMut(&GameEvent) + Send>;
+
+/// The listeners registered for the events of a game. Each event is handed
+/// to every listener in the order they were added.
+#[derive(Default)]
+pub struct EventListeners {
+    listeners: Vec<Listener>,
+}
+
+impl EventListeners {
+    pub fn add(&mut self, listener: impl FnMut(&GameEvent) + Send + 'static) {
+        self.listeners.push(Box::new(listener));
+    }
+
+    pub fn emit(&mut self, event: &GameEvent) {
+        for listener in &mut self.listeners {
+            listener(event);
+        }
+    }
+}
+
+/// The events for the tiles the last undo on `board` put back.
+pub fn undo_events(board: &Board) -> Vec<GameEvent> {
+    board
+        .last_restored()
+        .iter()
+        .map(|tile| GameEvent::TileUndone {
+            tile: *tile,
+            state: board.mine_state(tile),
+        })
+        .collect()
+}
+
+/// The events for the tiles the last redo on `board` changed again, the same
+/// ones the move sent when it was first made.
+pub fn redo_events(board: &Board) -> Vec<GameEvent> {
+    board
+        .last_restored()
+        .iter()
+        .map(|tile| match board.mine_state(tile) {
+            MineState::Revealed => GameEvent::TileRevealed { tile: *tile },
+            state => GameEvent::TileMarked { tile: *tile, state },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{GameView, MineGenerationError};
+    use std::sync::{Arc, Mutex};
+
+    struct NoView;
+
+    impl GameView for NoView {
+        type Error = MineGenerationError;
+
+        fn update_tile_with_state(
+            &mut self,
+            _tile_coordinate: &TileCoordinate,
+            _mine_state: MineState,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn update_tile_as_mine(
+            &mut self,
+            _tile_coordinate: &TileCoordinate,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn update_tile_with_mine_count(
+            &mut self,
+            _tile_coordinate: &TileCoordinate,
+            _num_mines: i32,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    fn tile(x: i32) -> TileCoordinate {
+        TileCoordinate { x, y: 0 }
+    }
+
+    #[test]
+    fn listeners_hear_every_event_in_the_order_they_were_added() {
+        let heard = Arc::new(Mutex::new(Vec::new()));
+        let mut listeners = EventListeners::default();
+        for name in ["first", "second"] {
+            let heard = heard.clone();
+            listeners.add(move |event| heard.lock().unwrap().push((name, *event)));
+        }
+
+        listeners.emit(&GameEvent::MinesGenerated);
+        listeners.emit(&GameEvent::AnimationCompleted);
+        assert_eq!(
+            *heard.lock().unwrap(),
+            vec![
+                ("first", GameEvent::MinesGenerated),
+                ("second", GameEvent::MinesGenerated),
+                ("first", GameEvent::AnimationCompleted),
+                ("second", GameEvent::AnimationCompleted),
+            ]
+        );
+    }
+
+    #[test]
+    fn undo_and_redo_send_the_tiles_they_change() {
+        // "0 1 * 1", where sweeping the zero opens up the first one.
+        let mut board = Board::new(BoardConfig::custom(4, 1, 1).unwrap(), 0);
+        board.preset_mines(vec![0, 0, 1, 0]).unwrap();
+        board.set_practice_mode(true);
+        board.sweep(&mut NoView, &tile(0)).unwrap();
+        board.cycle_mine_state(&mut NoView, &tile(2)).unwrap();
+
+        board.undo(&mut NoView).unwrap();
+        assert_eq!(
+            undo_events(&board),
+            vec![GameEvent::TileUndone {
+                tile: tile(2),
+                state: MineState::Empty
+            }]
+        );
+        board.undo(&mut NoView).unwrap();
+        assert_eq!(
+            undo_events(&board),
+            vec![
+                GameEvent::TileUndone {
+                    tile: tile(1),
+                    state: MineState::Empty
+                },
+                GameEvent::TileUndone {
+                    tile: tile(0),
+                    state: MineState::Empty
+                },
+            ]
+        );
+        // There's nothing left to undo.
+        board.undo(&mut NoView).unwrap();
+        assert!(undo_events(&board).is_empty());
+
+        board.redo(&mut NoView).unwrap();
+        assert_eq!(
+            redo_events(&board),
+            vec![
+                GameEvent::TileRevealed { tile: tile(0) },
+                GameEvent::TileRevealed { tile: tile(1) },
+            ]
+        );
+        board.redo(&mut NoView).unwrap();
+        assert_eq!(
+            redo_events(&board),
+            vec![GameEvent::TileMarked {
+                tile: tile(2),
+                state: MineState::Flag(1)
+            }]
+        );
+    }
+}
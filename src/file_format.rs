@@ -0,0 +1,387 @@
+use crate::board::{BoardConfig, BoardConfigError, FirstClickPolicy, GenerationMode, IndexHelper};
+use crate::topology::{TileShape, Topology};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+// The pieces shared by every file the game writes. Each file starts with a
+// "minesweeper-rs <kind> <version>" line, followed by one field per line, and
+// boards are written one row per line.
+
+#[derive(Debug)]
+pub enum FileError {
+    Io(io::Error),
+    UnsupportedVersion(String),
+    Malformed { line: usize },
+    InvalidBoard(BoardConfigError),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Io(error) => write!(f, "could not access the file: {}", error),
+            FileError::UnsupportedVersion(version) => {
+                write!(f, "version {} of this file is not supported", version)
+            }
+            FileError::Malformed { line } => write!(f, "the file is malformed on line {}", line),
+            FileError::InvalidBoard(error) => write!(f, "the board is invalid: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for FileError {}
+
+impl From<io::Error> for FileError {
+    fn from(error: io::Error) -> Self {
+        FileError::Io(error)
+    }
+}
+
+impl From<BoardConfigError> for FileError {
+    fn from(error: BoardConfigError) -> Self {
+        FileError::InvalidBoard(error)
+    }
+}
+
+/// How a board was set up, which is all it takes to reproduce its mines.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct BoardSettings {
+    pub config: BoardConfig,
+    pub seed: u64,
+    pub first_click_policy: FirstClickPolicy,
+    pub generation_mode: GenerationMode,
+    pub topology: Topology,
+    pub max_mines_per_tile: i32,
+    pub lives: i32,
+}
+
+pub fn header_line(kind: &str, version: u32) -> String {
+    format!("minesweeper-rs {} {}", kind, version)
+}
+
+pub fn settings_lines(settings: &BoardSettings) -> Vec<String> {
+    let config = settings.config;
+    vec![
+        format!(
+            "board {} {} {}",
+            config.width(),
+            config.height(),
+            config.num_mines()
+        ),
+        format!("seed {}", settings.seed),
+        format!(
+            "first-click {}",
+            match settings.first_click_policy {
+                FirstClickPolicy::Unprotected => "unprotected",
+                FirstClickPolicy::SafeTile => "safe",
+                FirstClickPolicy::SafeOpening => "opening",
+            }
+        ),
+        match settings.generation_mode {
+            GenerationMode::Random => "generation random".to_owned(),
+            GenerationMode::NoGuess { max_attempts } => {
+                format!("generation no-guess {}", max_attempts)
+            }
+        },
+        format!(
+            "topology {}{}{}",
+            match settings.topology.shape {
+                TileShape::Square => "square",
+                TileShape::Hex => "hex",
+            },
+            if settings.topology.wraps { " wrap" } else { "" },
+            match &settings.topology.neighborhood {
+                Some(neighborhood) => format!(" neighbors {}", neighborhood),
+                None => String::new(),
+            }
+        ),
+        format!("mines-per-tile {}", settings.max_mines_per_tile),
+        format!("lives {}", settings.lives),
+    ]
+}
+
+/// Writes a value per tile, one row per line, from a vector in board index order.
+pub fn grid_lines<T: Copy>(
+    config: &BoardConfig,
+    values: &[T],
+    to_char: impl Fn(T) -> char,
+) -> Vec<String> {
+    let index_helper = IndexHelper::new(config.width(), config.height());
+    (0..config.height())
+        .map(|y| {
+            (0..config.width())
+                .map(|x| to_char(values[index_helper.compute_index(x, y)]))
+                .collect()
+        })
+        .collect()
+}
+
+/// A tile without mines is `.` and one with a single mine is `*`. Tiles with
+/// more mines than that are written as the number of mines.
+pub fn mine_to_char(mines: i32) -> char {
+    match mines {
+        0 => '.',
+        1 => '*',
+        mines => char::from_digit(mines as u32, 10).unwrap_or('*'),
+    }
+}
+
+pub fn mine_from_char(c: char) -> Option<i32> {
+    match c {
+        '.' => Some(0),
+        '*' => Some(1),
+        '2'..='9' => c.to_digit(10).map(|mines| mines as i32),
+        _ => None,
+    }
+}
+
+/// Walks the lines of a file, remembering the current line number so that
+/// errors can point at it.
+pub(crate) struct Lines<'a> {
+    lines: std::str::Lines<'a>,
+    line_number: usize,
+}
+
+impl<'a> Lines<'a> {
+    /// Starts reading a file, checking that it is of the given kind and version.
+    pub(crate) fn new(text: &'a str, kind: &str, version: u32) -> Result<Self, FileError> {
+        let mut result = Self {
+            lines: text.lines(),
+            line_number: 0,
+        };
+
+        let found = match result.field("minesweeper-rs")?.as_slice() {
+            [found_kind, found] if *found_kind == kind => found.to_string(),
+            _ => return Err(result.malformed()),
+        };
+        if found != version.to_string() {
+            return Err(FileError::UnsupportedVersion(found));
+        }
+
+        Ok(result)
+    }
+
+    pub(crate) fn malformed(&self) -> FileError {
+        FileError::Malformed {
+            line: self.line_number,
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> Result<&'a str, FileError> {
+        self.line_number += 1;
+        self.lines.next().ok_or_else(|| self.malformed())
+    }
+
+    /// Reads the next line if there is one.
+    pub(crate) fn try_next(&mut self) -> Option<&'a str> {
+        let line = self.lines.next()?;
+        self.line_number += 1;
+        Some(line)
+    }
+
+    /// Reads a "<name> <values...>" line and returns the values.
+    pub(crate) fn field(&mut self, name: &str) -> Result<Vec<&'a str>, FileError> {
+        let mut words = self.next()?.split_whitespace();
+        if words.next() != Some(name) {
+            return Err(self.malformed());
+        }
+        Ok(words.collect())
+    }
+
+    pub(crate) fn parse<T: std::str::FromStr>(&self, value: &str) -> Result<T, FileError> {
+        value.parse().map_err(|_| self.malformed())
+    }
+
+    /// Reads the lines written by `settings_lines`.
+    pub(crate) fn settings(&mut self) -> Result<BoardSettings, FileError> {
+        let config = match self.field("board")?.as_slice() {
+            [width, height, mines] => {
+                BoardConfig::custom(self.parse(width)?, self.parse(height)?, self.parse(mines)?)?
+            }
+            _ => return Err(self.malformed()),
+        };
+        let seed = match self.field("seed")?.as_slice() {
+            [seed] => self.parse(seed)?,
+            _ => return Err(self.malformed()),
+        };
+        let first_click_policy = match self.field("first-click")?.as_slice() {
+            ["unprotected"] => FirstClickPolicy::Unprotected,
+            ["safe"] => FirstClickPolicy::SafeTile,
+            ["opening"] => FirstClickPolicy::SafeOpening,
+            _ => return Err(self.malformed()),
+        };
+        let generation_mode = match self.field("generation")?.as_slice() {
+            ["random"] => GenerationMode::Random,
+            ["no-guess", max_attempts] => GenerationMode::NoGuess {
+                max_attempts: self.parse(max_attempts)?,
+            },
+            _ => return Err(self.malformed()),
+        };
+        let topology = match self.field("topology")?.as_slice() {
+            [shape, rest @ ..] => {
+                let (wraps, rest) = match rest {
+                    ["wrap", rest @ ..] => (true, rest),
+                    rest => (false, rest),
+                };
+                let mut topology = Topology::new(
+                    match *shape {
+                        "square" => TileShape::Square,
+                        "hex" => TileShape::Hex,
+                        _ => return Err(self.malformed()),
+                    },
+                    wraps,
+                );
+                topology.neighborhood = match rest {
+                    [] => None,
+                    ["neighbors", offsets @ ..] => {
+                        Some(offsets.join(" ").parse().map_err(|_| self.malformed())?)
+                    }
+                    _ => return Err(self.malformed()),
+                };
+                topology
+            }
+            _ => return Err(self.malformed()),
+        };
+        if !topology.fits(&config) {
+            return Err(self.malformed());
+        }
+        let max_mines_per_tile = match self.field("mines-per-tile")?.as_slice() {
+            [max_mines_per_tile] => self.parse(max_mines_per_tile)?,
+            _ => return Err(self.malformed()),
+        };
+        if !(1..=BoardConfig::MAX_MINES_PER_TILE).contains(&max_mines_per_tile) {
+            return Err(self.malformed());
+        }
+        let lives = match self.field("lives")?.as_slice() {
+            [lives] => self.parse(lives)?,
+            _ => return Err(self.malformed()),
+        };
+        if lives < 1 {
+            return Err(self.malformed());
+        }
+        if first_click_policy
+            .check_room(&config, &topology, max_mines_per_tile)
+            .is_err()
+        {
+            return Err(self.malformed());
+        }
+
+        Ok(BoardSettings {
+            config,
+            seed,
+            first_click_policy,
+            generation_mode,
+            topology,
+            max_mines_per_tile,
+            lives,
+        })
+    }
+
+    /// Reads the lines written by `grid_lines` into a vector in board index order.
+    pub(crate) fn grid<T: Copy>(
+        &mut self,
+        config: &BoardConfig,
+        fill: T,
+        from_char: impl Fn(char) -> Option<T>,
+    ) -> Result<Vec<T>, FileError> {
+        let index_helper = IndexHelper::new(config.width(), config.height());
+        let mut result = vec![fill; config.tile_count()];
+        for y in 0..config.height() {
+            let row = self.next()?;
+            if row.chars().count() != config.width() as usize {
+                return Err(self.malformed());
+            }
+            for (x, c) in row.chars().enumerate() {
+                let value = from_char(c).ok_or_else(|| self.malformed())?;
+                result[index_helper.compute_index(x as i32, y)] = value;
+            }
+        }
+        Ok(result)
+    }
+}
+
+/// Where the game keeps its files: `%LOCALAPPDATA%\minesweeper-rs`, or
+/// `$HOME/.minesweeper-rs` where that isn't set.
+pub fn data_dir() -> Option<PathBuf> {
+    if let Some(local_app_data) = std::env::var_os("LOCALAPPDATA") {
+        Some(PathBuf::from(local_app_data).join("minesweeper-rs"))
+    } else {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".minesweeper-rs"))
+    }
+}
+
+/// Reads a file from the data directory, or `None` if it doesn't exist.
+pub fn read_data_file(name: &str) -> Result<Option<String>, FileError> {
+    let path = match data_dir() {
+        Some(dir) => dir.join(name),
+        None => return Ok(None),
+    };
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Replaces a file in the data directory. The contents are written next to
+/// the real file first so that a crash can't leave half a file behind.
+pub fn write_data_file(name: &str, contents: impl AsRef<[u8]>) -> Result<(), FileError> {
+    let dir = match data_dir() {
+        Some(dir) => dir,
+        None => return Ok(()),
+    };
+    let path = dir.join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temp_path = dir.join(format!("{}.tmp", name));
+    fs::write(&temp_path, contents)?;
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+/// Adds a line to the end of a file in the data directory, starting the file
+/// with `header` if it doesn't exist yet. Lines already in the file are never
+/// touched, so a bad write can only lose the line being written.
+pub fn append_data_file(name: &str, header: &str, line: &str) -> Result<(), FileError> {
+    let dir = match data_dir() {
+        Some(dir) => dir,
+        None => return Ok(()),
+    };
+    fs::create_dir_all(&dir)?;
+
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .create(true)
+        .append(true)
+        .open(dir.join(name))?;
+    let len = file.metadata()?.len();
+    if len == 0 {
+        writeln!(file, "{}", header)?;
+    } else {
+        // Finish off a line that was cut short, so that it doesn't take the new one with it.
+        let mut last_byte = [0];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last_byte)?;
+        if last_byte[0] != b'\n' {
+            writeln!(file)?;
+        }
+    }
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+/// Removes a file from the data directory, if it exists.
+pub fn remove_data_file(name: &str) -> Result<(), FileError> {
+    let path = match data_dir() {
+        Some(dir) => dir.join(name),
+        None => return Ok(()),
+    };
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error.into()),
+    }
+}
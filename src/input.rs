@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+
+/// A key as the frontends see it, whatever the platform calls it.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Escape,
+    /// A function key, numbered from 1.
+    F(u8),
+    /// A key that types a character, including space. Letters are the same
+    /// key whether they're upper or lower case.
+    Char(char),
+    /// A character key pressed while holding control.
+    Ctrl(char),
+}
+
+impl Key {
+    fn normalized(self) -> Self {
+        match self {
+            Key::Char(c) => Key::Char(c.to_ascii_lowercase()),
+            Key::Ctrl(c) => Key::Ctrl(c.to_ascii_lowercase()),
+            key => key,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    /// How far a step in this direction goes along each axis.
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
+}
+
+/// What the player can do from the keyboard. Revealing, marking and
+/// chording apply to the selected tile.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Command {
+    MoveSelection(Direction),
+    Reveal,
+    CycleMark,
+    Chord,
+    NewGame,
+    /// Only does anything in practice mode.
+    Undo,
+    /// Only does anything in practice mode.
+    Redo,
+    /// Saves the layout of the last game as an `.mbf` board.
+    ExportBoard,
+    Quit,
+}
+
+/// Which key does what. By default the arrow keys and WASD move the
+/// selection, space and enter reveal, F flags, C chords, N or F2 start a new
+/// game, and Ctrl+Z and Ctrl+Y undo and redo. Exporting and quitting are left
+/// for each frontend to bind.
+#[derive(Clone, PartialEq, Debug)]
+pub struct KeyBindings {
+    bindings: HashMap<Key, Command>,
+}
+
+impl KeyBindings {
+    /// Bindings without any keys, to build up from scratch.
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Makes the key carry out the command, replacing whatever it did before.
+    pub fn bind(&mut self, key: Key, command: Command) {
+        self.bindings.insert(key.normalized(), command);
+    }
+
+    pub fn unbind(&mut self, key: Key) {
+        self.bindings.remove(&key.normalized());
+    }
+
+    pub fn command(&self, key: Key) -> Option<Command> {
+        self.bindings.get(&key.normalized()).copied()
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let mut result = Self::empty();
+        for (keys, direction) in [
+            ([Key::Up, Key::Char('w')], Direction::Up),
+            ([Key::Down, Key::Char('s')], Direction::Down),
+            ([Key::Left, Key::Char('a')], Direction::Left),
+            ([Key::Right, Key::Char('d')], Direction::Right),
+        ] {
+            for key in keys {
+                result.bind(key, Command::MoveSelection(direction));
+            }
+        }
+        result.bind(Key::Char(' '), Command::Reveal);
+        result.bind(Key::Enter, Command::Reveal);
+        result.bind(Key::Char('f'), Command::CycleMark);
+        result.bind(Key::Char('c'), Command::Chord);
+        result.bind(Key::Char('n'), Command::NewGame);
+        result.bind(Key::F(2), Command::NewGame);
+        result.bind(Key::Ctrl('z'), Command::Undo);
+        result.bind(Key::Ctrl('y'), Command::Redo);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings() {
+        let bindings = KeyBindings::default();
+        assert_eq!(
+            bindings.command(Key::Up),
+            Some(Command::MoveSelection(Direction::Up))
+        );
+        assert_eq!(
+            bindings.command(Key::Char('d')),
+            Some(Command::MoveSelection(Direction::Right))
+        );
+        assert_eq!(bindings.command(Key::Char(' ')), Some(Command::Reveal));
+        assert_eq!(bindings.command(Key::Enter), Some(Command::Reveal));
+        assert_eq!(bindings.command(Key::Char('f')), Some(Command::CycleMark));
+        assert_eq!(bindings.command(Key::Char('c')), Some(Command::Chord));
+        assert_eq!(bindings.command(Key::F(2)), Some(Command::NewGame));
+        assert_eq!(bindings.command(Key::Ctrl('z')), Some(Command::Undo));
+        assert_eq!(bindings.command(Key::Ctrl('y')), Some(Command::Redo));
+        assert_eq!(bindings.command(Key::Char('z')), None);
+        assert_eq!(bindings.command(Key::Escape), None);
+    }
+
+    #[test]
+    fn letters_ignore_case() {
+        let mut bindings = KeyBindings::empty();
+        bindings.bind(Key::Char('Q'), Command::Quit);
+        bindings.bind(Key::Ctrl('E'), Command::ExportBoard);
+
+        assert_eq!(bindings.command(Key::Char('q')), Some(Command::Quit));
+        assert_eq!(bindings.command(Key::Char('Q')), Some(Command::Quit));
+        assert_eq!(bindings.command(Key::Ctrl('e')), Some(Command::ExportBoard));
+        assert_eq!(bindings.command(Key::Char('e')), None);
+    }
+
+    #[test]
+    fn rebinding_replaces_the_old_command() {
+        let mut bindings = KeyBindings::default();
+        bindings.bind(Key::Char('F'), Command::Chord);
+        assert_eq!(bindings.command(Key::Char('f')), Some(Command::Chord));
+
+        bindings.unbind(Key::Char('C'));
+        assert_eq!(bindings.command(Key::Char('c')), None);
+        // Other keys for the same command keep working.
+        bindings.unbind(Key::Enter);
+        assert_eq!(bindings.command(Key::Char(' ')), Some(Command::Reveal));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::board::{IndexHelper, TileCoordinate};
+use crate::topology::Topology;
+
+/// A position or a size on screen, in the units of the window.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+}
+
+/// Where the tiles of a board go on screen, and which tile is under a point.
+///
+/// Each tile takes up its size plus the margin, with half of the margin on
+/// either side of it. On hex boards every odd row is shifted right by half a
+/// tile, so that each tile touches two tiles above it and two below it like
+/// the bricks in a wall.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct GridLayout {
+    index_helper: IndexHelper,
+    topology: Topology,
+    tile_size: Point,
+    margin: Point,
+}
+
+impl GridLayout {
+    pub fn new(
+        width_in_tiles: i32,
+        height_in_tiles: i32,
+        topology: Topology,
+        tile_size: Point,
+        margin: Point,
+    ) -> Self {
+        Self {
+            index_helper: IndexHelper::new(width_in_tiles, height_in_tiles),
+            topology,
+            tile_size,
+            margin,
+        }
+    }
+
+    pub fn index_helper(&self) -> &IndexHelper {
+        &self.index_helper
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn tile_size(&self) -> Point {
+        self.tile_size
+    }
+
+    pub fn margin(&self) -> Point {
+        self.margin
+    }
+
+    /// The size of the whole grid before it's scaled.
+    pub fn size(&self) -> Point {
+        let mut width_in_tiles = self.index_helper.width() as f32;
+        if self.topology.has_shifted_rows() && self.index_helper.height() > 1 {
+            width_in_tiles += 0.5;
+        }
+        Point::new(
+            (self.tile_size.x + self.margin.x) * width_in_tiles,
+            (self.tile_size.y + self.margin.y) * self.index_helper.height() as f32,
+        )
+    }
+
+    /// Where the top left corner of a tile goes, relative to the grid.
+    pub fn tile_offset(&self, tile_coordinate: &TileCoordinate) -> Point {
+        Point::new(
+            self.margin.x / 2.0
+                + self.row_shift(tile_coordinate.y)
+                + (self.tile_size.x + self.margin.x) * tile_coordinate.x as f32,
+            self.margin.y / 2.0 + (self.tile_size.y + self.margin.y) * tile_coordinate.y as f32,
+        )
+    }
+
+    /// Finds the tile under a point on the unscaled grid. Points on the margin
+    /// count towards the closest tile.
+    pub fn hit_test(&self, point: Point) -> Option<TileCoordinate> {
+        let y = (point.y / (self.tile_size.y + self.margin.y)).floor() as i32;
+        let x = ((point.x - self.row_shift(y)) / (self.tile_size.x + self.margin.x)).floor() as i32;
+
+        if self.index_helper.is_in_bounds(x, y) {
+            Some(TileCoordinate { x, y })
+        } else {
+            None
+        }
+    }
+
+    fn row_shift(&self, y: i32) -> f32 {
+        if self.topology.has_shifted_rows() && y % 2 != 0 {
+            (self.tile_size.x + self.margin.x) / 2.0
+        } else {
+            0.0
+        }
+    }
+
+    /// How much the grid has to be scaled to fill a window while keeping its
+    /// aspect ratio, with `padding` left around it.
+    pub fn scale_to_fit(&self, window_size: Point, padding: Point) -> f32 {
+        let size = self.size();
+        let board_size = Point::new(size.x + padding.x, size.y + padding.y);
+
+        let window_ratio = window_size.x / window_size.y;
+        let board_ratio = board_size.x / board_size.y;
+
+        if window_ratio > board_ratio {
+            window_size.y / board_size.y
+        } else {
+            window_size.x / board_size.x
+        }
+    }
+
+    /// Finds the tile under a point in a window that shows the grid scaled
+    /// to fit and centered.
+    pub fn hit_test_in_window(
+        &self,
+        point: Point,
+        window_size: Point,
+        padding: Point,
+    ) -> Option<TileCoordinate> {
+        let scale = self.scale_to_fit(window_size, padding);
+        let size = self.size();
+        let offset_x = (window_size.x - size.x * scale) / 2.0;
+        let offset_y = (window_size.y - size.y * scale) / 2.0;
+
+        self.hit_test(Point::new(
+            (point.x - offset_x) / scale,
+            (point.y - offset_y) / scale,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::TileShape;
+
+    // Tiles 10 wide and tall with 2 between them, so each one takes up 12.
+    fn layout(topology: Topology) -> GridLayout {
+        GridLayout::new(3, 2, topology, Point::new(10.0, 10.0), Point::new(2.0, 2.0))
+    }
+
+    fn hit(layout: &GridLayout, x: f32, y: f32) -> Option<(i32, i32)> {
+        layout
+            .hit_test(Point::new(x, y))
+            .map(|tile| (tile.x, tile.y))
+    }
+
+    #[test]
+    fn hit_test_finds_tiles_up_to_their_edges() {
+        let layout = layout(Topology::default());
+        assert_eq!(hit(&layout, 1.0, 1.0), Some((0, 0)));
+        assert_eq!(hit(&layout, 11.9, 11.9), Some((0, 0)));
+        assert_eq!(hit(&layout, 12.0, 0.0), Some((1, 0)));
+        assert_eq!(hit(&layout, 35.9, 23.9), Some((2, 1)));
+    }
+
+    #[test]
+    fn hit_test_gives_the_margin_to_the_closest_tile() {
+        let layout = layout(Topology::default());
+        // The gap between the first two tiles runs from 11 to 13.
+        assert_eq!(hit(&layout, 11.5, 5.0), Some((0, 0)));
+        assert_eq!(hit(&layout, 12.5, 5.0), Some((1, 0)));
+        assert_eq!(hit(&layout, 0.5, 0.5), Some((0, 0)));
+    }
+
+    #[test]
+    fn hit_test_misses_outside_of_the_grid() {
+        let layout = layout(Topology::default());
+        assert_eq!(hit(&layout, -0.1, 5.0), None);
+        assert_eq!(hit(&layout, 5.0, -0.1), None);
+        assert_eq!(hit(&layout, 36.0, 5.0), None);
+        assert_eq!(hit(&layout, 5.0, 24.0), None);
+    }
+
+    #[test]
+    fn hit_test_follows_the_shifted_hex_rows() {
+        let layout = layout(Topology::new(TileShape::Hex, false));
+        assert_eq!(layout.size(), Point::new(42.0, 24.0));
+
+        // Even rows start at the left edge.
+        assert_eq!(hit(&layout, 3.0, 1.0), Some((0, 0)));
+        assert_eq!(hit(&layout, 40.0, 1.0), None);
+        // Odd rows are half a tile further right.
+        assert_eq!(hit(&layout, 3.0, 13.0), None);
+        assert_eq!(hit(&layout, 6.0, 13.0), Some((0, 1)));
+        assert_eq!(hit(&layout, 40.0, 13.0), Some((2, 1)));
+        assert_eq!(
+            layout.tile_offset(&TileCoordinate { x: 1, y: 1 }),
+            Point::new(19.0, 13.0)
+        );
+    }
+
+    #[test]
+    fn hit_test_in_window_undoes_the_scale_and_centering() {
+        let layout = layout(Topology::default());
+        let no_padding = Point::new(0.0, 0.0);
+        let hit = |x: f32, y: f32, window_size: Point| {
+            layout
+                .hit_test_in_window(Point::new(x, y), window_size, no_padding)
+                .map(|tile| (tile.x, tile.y))
+        };
+
+        // The grid is 36x24, so it fills this window at twice its size.
+        let window = Point::new(72.0, 48.0);
+        assert_eq!(hit(25.0, 25.0, window), Some((1, 1)));
+        assert_eq!(hit(71.0, 47.0, window), Some((2, 1)));
+
+        // A wider window leaves 14 on either side of the grid.
+        let window = Point::new(100.0, 48.0);
+        assert_eq!(hit(10.0, 10.0, window), None);
+        assert_eq!(hit(15.0, 1.0, window), Some((0, 0)));
+        assert_eq!(hit(39.0, 25.0, window), Some((1, 1)));
+        assert_eq!(hit(90.0, 10.0, window), None);
+    }
+}
@@ -0,0 +1,17 @@
+//! The rules of Minesweeper and everything around them that doesn't depend on
+//! how the game is drawn: solving, statistics, hit testing, and the files the
+//! game reads and writes. The frontends live in the binaries.
+
+pub mod board;
+pub mod events;
+pub mod file_format;
+pub mod input;
+pub mod layout;
+pub mod mbf;
+pub mod options;
+pub mod records;
+pub mod replay;
+pub mod save;
+pub mod solver;
+pub mod stats;
+pub mod topology;
@@ -1,29 +1,49 @@
-#![windows_subsystem = "windows"]
+#![cfg_attr(windows, windows_subsystem = "windows")]
 
+// The windowed game only exists on Windows. Everywhere else this binary
+// just points at the terminal version.
+#[cfg(windows)]
 mod comp_assets;
+#[cfg(windows)]
 mod comp_ui;
+#[cfg(windows)]
 mod interop;
+#[cfg(windows)]
 mod minesweeper;
+#[cfg(windows)]
 mod numerics;
+#[cfg(windows)]
 mod visual_grid;
+#[cfg(windows)]
 mod window;
 
+#[cfg(windows)]
 use interop::{
     create_dispatcher_queue_controller_for_current_thread,
     shutdown_dispatcher_queue_controller_and_exit,
 };
-use minesweeper::Minesweeper;
+#[cfg(windows)]
+use minesweeper::{invalid_arg, Minesweeper};
+#[cfg(windows)]
+use minesweeper_rs::{mbf, options::Options, replay, save};
+#[cfg(windows)]
 use window::Window;
+#[cfg(windows)]
 use windows::{
-    core::Result,
+    core::{w, Result},
     Win32::{
         System::WinRT::{RoInitialize, RO_INIT_SINGLETHREADED},
-        UI::WindowsAndMessaging::{DispatchMessageW, GetMessageW, TranslateMessage, MSG},
+        UI::WindowsAndMessaging::{
+            DispatchMessageW, GetMessageW, MessageBoxW, TranslateMessage, IDYES, MB_ICONQUESTION,
+            MB_YESNO, MSG,
+        },
     },
     UI::Composition::Compositor,
 };
+#[cfg(windows)]
 use windows_numerics::Vector2;
 
+#[cfg(windows)]
 fn run() -> Result<()> {
     unsafe { RoInitialize(RO_INIT_SINGLETHREADED)? };
     let controller = create_dispatcher_queue_controller_for_current_thread()?;
@@ -40,7 +60,27 @@ fn run() -> Result<()> {
     let root = compositor.CreateContainerVisual()?;
     root.SetRelativeSizeAdjustment(Vector2::new(1.0, 1.0))?;
 
-    let game = Minesweeper::new(&root, &window_size)?;
+    let options = Options::from_args(std::env::args().skip(1)).map_err(invalid_arg)?;
+    let seed = options.seed.unwrap_or_else(rand::random);
+    let mut game = Minesweeper::new(&root, &window_size, options.config, seed)?;
+    game.set_first_click_policy(options.first_click_policy);
+    game.set_generation_mode(options.generation_mode);
+    game.set_topology(options.topology)?;
+    game.set_max_mines_per_tile(options.max_mines_per_tile)?;
+    game.set_lives(options.lives);
+    game.set_practice_mode(options.practice_mode);
+
+    if let Some(path) = &options.replay {
+        let replay = replay::load(path).map_err(invalid_arg)?;
+        game.play_replay(replay, options.replay_speed)?;
+    } else if let Some(path) = &options.mbf {
+        game.load_board(mbf::load(path).map_err(invalid_arg)?)?;
+    } else if let Ok(Some(saved)) = save::load() {
+        // A save that can't be read is simply ignored, it gets replaced when this game is closed.
+        if ask_to_resume() {
+            game.resume(saved)?;
+        }
+    }
 
     let window = Window::new("Minesweeper", window_width, window_height, game)?;
     let target = window.create_window_target(&compositor, false)?;
@@ -56,6 +96,20 @@ fn run() -> Result<()> {
     shutdown_dispatcher_queue_controller_and_exit(&controller, message.wParam.0 as i32);
 }
 
+#[cfg(windows)]
+fn ask_to_resume() -> bool {
+    let result = unsafe {
+        MessageBoxW(
+            None,
+            w!("Do you want to continue your last game?"),
+            w!("Minesweeper"),
+            MB_YESNO | MB_ICONQUESTION,
+        )
+    };
+    result == IDYES
+}
+
+#[cfg(windows)]
 fn main() {
     let result = run();
 
@@ -64,3 +118,9 @@ fn main() {
         error.code().unwrap();
     }
 }
+
+#[cfg(not(windows))]
+fn main() {
+    eprintln!("The windowed game needs Windows, use minesweeper-term to play in a terminal.");
+    std::process::exit(1);
+}
@@ -0,0 +1,243 @@
+use crate::board::{BoardConfig, BoardConfigError, IndexHelper};
+use crate::file_format::{self, FileError};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MBF_DIR_NAME: &str = "boards";
+
+/// A layout of mines in the `.mbf` format shared by other Minesweeper tools:
+/// a byte each for the width and height, the number of mines as a big endian
+/// `u16`, and then an `x` and `y` byte for every mine.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MbfBoard {
+    pub config: BoardConfig,
+    /// One entry per tile, in board index order.
+    pub mines: Vec<bool>,
+}
+
+#[derive(Debug)]
+pub enum MbfError {
+    File(FileError),
+    Truncated {
+        expected: usize,
+        found: usize,
+    },
+    InvalidBoard(BoardConfigError),
+    MineOutOfBounds {
+        x: u8,
+        y: u8,
+    },
+    DuplicateMine {
+        x: u8,
+        y: u8,
+    },
+    TooLarge {
+        width: i32,
+        height: i32,
+        num_mines: i32,
+    },
+}
+
+impl fmt::Display for MbfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MbfError::File(error) => write!(f, "{}", error),
+            MbfError::Truncated { expected, found } => write!(
+                f,
+                "the board needs {} bytes, but only {} were found",
+                expected, found
+            ),
+            MbfError::InvalidBoard(error) => write!(f, "the board is invalid: {}", error),
+            MbfError::MineOutOfBounds { x, y } => {
+                write!(f, "the mine at {}, {} is outside of the board", x, y)
+            }
+            MbfError::DuplicateMine { x, y } => {
+                write!(f, "the mine at {}, {} is listed more than once", x, y)
+            }
+            MbfError::TooLarge {
+                width,
+                height,
+                num_mines,
+            } => write!(
+                f,
+                "a {}x{} board with {} mines doesn't fit in the format",
+                width, height, num_mines
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MbfError {}
+
+impl From<FileError> for MbfError {
+    fn from(error: FileError) -> Self {
+        MbfError::File(error)
+    }
+}
+
+impl From<BoardConfigError> for MbfError {
+    fn from(error: BoardConfigError) -> Self {
+        MbfError::InvalidBoard(error)
+    }
+}
+
+impl MbfBoard {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MbfError> {
+        const HEADER_LEN: usize = 4;
+        if bytes.len() < HEADER_LEN {
+            return Err(MbfError::Truncated {
+                expected: HEADER_LEN,
+                found: bytes.len(),
+            });
+        }
+
+        let width = bytes[0] as i32;
+        let height = bytes[1] as i32;
+        let num_mines = u16::from_be_bytes([bytes[2], bytes[3]]) as i32;
+        let expected = HEADER_LEN + num_mines as usize * 2;
+        if bytes.len() < expected {
+            return Err(MbfError::Truncated {
+                expected,
+                found: bytes.len(),
+            });
+        }
+        let config = BoardConfig::custom(width, height, num_mines)?;
+
+        let index_helper = IndexHelper::new(width, height);
+        let mut mines = vec![false; config.tile_count()];
+        for coordinate in bytes[HEADER_LEN..expected].chunks_exact(2) {
+            let (x, y) = (coordinate[0], coordinate[1]);
+            if !index_helper.is_in_bounds(x as i32, y as i32) {
+                return Err(MbfError::MineOutOfBounds { x, y });
+            }
+            let index = index_helper.compute_index(x as i32, y as i32);
+            if mines[index] {
+                return Err(MbfError::DuplicateMine { x, y });
+            }
+            mines[index] = true;
+        }
+
+        Ok(Self { config, mines })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MbfError> {
+        let config = self.config;
+        let too_large = MbfError::TooLarge {
+            width: config.width(),
+            height: config.height(),
+            num_mines: config.num_mines(),
+        };
+        let (width, height, num_mines) = match (
+            u8::try_from(config.width()),
+            u8::try_from(config.height()),
+            u16::try_from(config.num_mines()),
+        ) {
+            (Ok(width), Ok(height), Ok(num_mines)) => (width, height, num_mines),
+            _ => return Err(too_large),
+        };
+
+        let mut bytes = vec![width, height];
+        bytes.extend_from_slice(&num_mines.to_be_bytes());
+        let index_helper = IndexHelper::new(config.width(), config.height());
+        for (index, _) in self.mines.iter().enumerate().filter(|(_, mine)| **mine) {
+            bytes.push(index_helper.compute_x_from_index(index) as u8);
+            bytes.push(index_helper.compute_y_from_index(index) as u8);
+        }
+
+        Ok(bytes)
+    }
+}
+
+pub fn load(path: &Path) -> Result<MbfBoard, MbfError> {
+    let bytes = fs::read(path).map_err(FileError::from)?;
+    MbfBoard::from_bytes(&bytes)
+}
+
+/// Keeps a board in the boards folder of the data directory, named after
+/// when it was stored.
+pub fn store(board: &MbfBoard) -> Result<(), MbfError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let name = format!(
+        "{}/{}-{}x{}.mbf",
+        MBF_DIR_NAME,
+        now.as_millis(),
+        board.config.width(),
+        board.config.height()
+    );
+    file_format::write_data_file(&name, board.to_bytes()?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 3x2 board with mines at 2, 0 and 0, 1.
+    const BYTES: [u8; 8] = [3, 2, 0, 2, 2, 0, 0, 1];
+
+    #[test]
+    fn reads_the_mines() {
+        let board = MbfBoard::from_bytes(&BYTES).unwrap();
+        assert_eq!(board.config, BoardConfig::custom(3, 2, 2).unwrap());
+        // Board index order runs down each column.
+        assert_eq!(board.mines, vec![false, true, false, false, true, false]);
+    }
+
+    #[test]
+    fn round_trips() {
+        let board = MbfBoard::from_bytes(&BYTES).unwrap();
+        let bytes = board.to_bytes().unwrap();
+        assert_eq!(MbfBoard::from_bytes(&bytes).unwrap(), board);
+    }
+
+    #[test]
+    fn rejects_a_truncated_header() {
+        assert!(matches!(
+            MbfBoard::from_bytes(&BYTES[..3]),
+            Err(MbfError::Truncated {
+                expected: 4,
+                found: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_mines() {
+        assert!(matches!(
+            MbfBoard::from_bytes(&BYTES[..7]),
+            Err(MbfError::Truncated {
+                expected: 8,
+                found: 7
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_a_duplicate_mine() {
+        assert!(matches!(
+            MbfBoard::from_bytes(&[3, 2, 0, 2, 2, 0, 2, 0]),
+            Err(MbfError::DuplicateMine { x: 2, y: 0 })
+        ));
+    }
+
+    #[test]
+    fn rejects_a_mine_outside_of_the_board() {
+        assert!(matches!(
+            MbfBoard::from_bytes(&[3, 2, 0, 2, 2, 0, 0, 2]),
+            Err(MbfError::MineOutOfBounds { x: 0, y: 2 })
+        ));
+    }
+
+    #[test]
+    fn rejects_a_board_too_large_to_write() {
+        let board = MbfBoard {
+            config: BoardConfig::custom(300, 2, 1).unwrap(),
+            mines: vec![false; 600],
+        };
+        assert!(matches!(board.to_bytes(), Err(MbfError::TooLarge { .. })));
+    }
+}
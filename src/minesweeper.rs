@@ -1,129 +1,99 @@
 use crate::comp_ui::CompUI;
-use crate::visual_grid::TileCoordinate;
-use rand::distr::{Distribution, Uniform};
-use std::collections::VecDeque;
-use windows::{core::Result, Graphics::SizeInt32, UI::Composition::ContainerVisual};
+use minesweeper_rs::board::{
+    Board, BoardConfig, FirstClickPolicy, GameView, GenerationMode, MineState, MoveOutcome,
+    TileCoordinate,
+};
+use minesweeper_rs::events::{self, EventListeners, GameEvent};
+use minesweeper_rs::file_format::BoardSettings;
+use minesweeper_rs::input::{Command, Direction};
+use minesweeper_rs::mbf::MbfBoard;
+use minesweeper_rs::records::{GameRecord, RecordRules};
+use minesweeper_rs::replay::{Replay, ReplayAction, ReplayPlayer, ReplayRecorder};
+use minesweeper_rs::save::SavedGame;
+use minesweeper_rs::stats::{ClickCounts, GameStats, Stopwatch};
+use minesweeper_rs::topology::Topology;
+use std::sync::{Arc, Mutex};
+use windows::{
+    core::{Error, Result},
+    Graphics::SizeInt32,
+    Win32::Foundation::E_INVALIDARG,
+    UI::Composition::ContainerVisual,
+};
 use windows_numerics::Vector2;
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash)]
-pub enum MineState {
-    Empty,
-    Flag,
-    Question,
-    Revealed,
-}
-impl MineState {
-    fn cycle(self) -> Self {
-        match self {
-            MineState::Empty => MineState::Flag,
-            MineState::Flag => MineState::Question,
-            MineState::Question => MineState::Empty,
-            MineState::Revealed => unreachable!("We shouldn't be cycling a revealed tile!"),
-        }
-    }
-}
-
-#[derive(Copy, Clone, PartialEq)]
-enum MineGenerationState {
-    Deferred,
-    Generated,
-}
-
-pub struct IndexHelper {
-    width: i32,
-    height: i32,
-}
-
-impl IndexHelper {
-    pub fn new(width: i32, height: i32) -> Self {
-        Self { width, height }
-    }
-
-    pub fn compute_index(&self, x: i32, y: i32) -> usize {
-        (x * self.height + y) as usize
-    }
-
-    pub fn compute_x_from_index(&self, index: usize) -> i32 {
-        index as i32 / self.height
-    }
-
-    pub fn compute_y_from_index(&self, index: usize) -> i32 {
-        index as i32 % self.height
-    }
-
-    pub fn is_in_bounds(&self, x: i32, y: i32) -> bool {
-        (x >= 0 && x < self.width) && (y >= 0 && y < self.height)
-    }
-}
-
 pub struct Minesweeper {
     ui: CompUI,
+    board: Board,
 
-    game_board_width: i32,
-    game_board_height: i32,
-    index_helper: IndexHelper,
-
-    mine_states: Vec<MineState>,
-    mines: Vec<bool>,
-    neighbor_counts: Vec<i32>,
-    mine_generation_state: MineGenerationState,
-    num_mines: i32,
     last_tile: Option<TileCoordinate>,
-
-    game_over: bool,
+    parent_size: Vector2,
+    stopwatch: Stopwatch,
+    clicks: ClickCounts,
+    game_stats: Option<GameStats>,
+    finished_record: Option<GameRecord>,
+
+    recorder: Option<ReplayRecorder>,
+    finished_replay: Option<Replay>,
+    player: Option<ReplayPlayer>,
+    played_own_game: bool,
+    playing_loaded_board: bool,
+    // The rules the player picked, while a loaded board or a replay is
+    // played by its own.
+    own_rules: Option<OwnRules>,
+
+    // Shared with the UI, which reports when its animations complete.
+    listeners: Arc<Mutex<EventListeners>>,
 }
 
 impl Minesweeper {
-    pub fn new(parent_visual: &ContainerVisual, parent_size: &Vector2) -> Result<Self> {
-        let game_board_size_in_tiles = SizeInt32 {
-            Width: 16,
-            Height: 16,
-        };
-        let ui = CompUI::new(parent_visual, parent_size, &game_board_size_in_tiles)?;
-
-        let tile_count =
-            (game_board_size_in_tiles.Width * game_board_size_in_tiles.Height) as usize;
+    pub fn new(
+        parent_visual: &ContainerVisual,
+        parent_size: &Vector2,
+        config: BoardConfig,
+        seed: u64,
+    ) -> Result<Self> {
+        let ui = CompUI::new(parent_visual, parent_size, &board_size_in_tiles(&config))?;
+        let board = Board::new(config, seed);
 
         let mut result = Self {
             ui,
+            board,
 
-            game_board_width: game_board_size_in_tiles.Width,
-            game_board_height: game_board_size_in_tiles.Height,
-            index_helper: IndexHelper::new(
-                game_board_size_in_tiles.Width,
-                game_board_size_in_tiles.Height,
-            ),
-
-            mine_states: vec![MineState::Empty; tile_count], // create vec of size tile_count filled with MineState::Empty
-            mines: vec![false; tile_count],
-            neighbor_counts: Vec::new(),
-            mine_generation_state: MineGenerationState::Deferred,
-            num_mines: 0,
             last_tile: None,
-
-            game_over: false,
+            parent_size: *parent_size,
+            stopwatch: Stopwatch::default(),
+            clicks: ClickCounts::default(),
+            game_stats: None,
+            finished_record: None,
+
+            recorder: None,
+            finished_replay: None,
+            player: None,
+            played_own_game: false,
+            playing_loaded_board: false,
+            own_rules: None,
+
+            listeners: Arc::default(),
         };
 
-        result.new_game(
-            game_board_size_in_tiles.Width,
-            game_board_size_in_tiles.Height,
-            40,
-        )?;
+        result.new_game_with_seed(config, seed)?;
         result.on_parent_size_changed(parent_size)?;
 
         Ok(result)
     }
 
     pub fn on_pointer_moved(&mut self, point: &Vector2) -> Result<()> {
-        if self.game_over || self.ui.is_animation_playing() {
+        if self.board.is_game_over() || self.ui.is_animation_playing() {
             return Ok(());
         }
+        self.record(ReplayAction::PointerMoved {
+            x: point.X,
+            y: point.Y,
+        });
 
-        let selected_tile = if let Some(tile) = self.ui.hit_test(point)? {
+        let selected_tile = if let Some(tile) = self.ui.hit_test(point) {
             self.last_tile = Some(tile);
-            if self.mine_states[self.index_helper.compute_index(tile.x, tile.y)]
-                != MineState::Revealed
-            {
+            if self.board.mine_state(&tile) != MineState::Revealed {
                 Some(tile)
             } else {
                 None
@@ -138,50 +108,30 @@ impl Minesweeper {
     }
 
     pub fn on_parent_size_changed(&mut self, new_size: &Vector2) -> Result<()> {
+        self.parent_size = *new_size;
+        self.record(ReplayAction::Resized {
+            width: new_size.X,
+            height: new_size.Y,
+        });
         self.ui.resize(new_size)?;
         Ok(())
     }
 
     pub fn on_pointer_pressed(&mut self, is_right_button: bool, is_eraser: bool) -> Result<()> {
-        // TODO: Switch the condition back once we can subscribe to events.
-        //if self.game_over && !self.ui.is_animation_playing() {
-        if self.game_over {
-            self.new_game(
-                self.game_board_width,
-                self.game_board_height,
-                self.num_mines,
-            )?;
+        if self.board.is_game_over() {
+            // Input stays locked until the animation at the end of the game is over.
+            if self.ui.is_animation_playing() {
+                return Ok(());
+            }
+            self.new_game(self.board.config())?;
         }
 
         let current_selection = self.ui.current_selected_tile();
         if let Some(current_selection) = current_selection {
-            let index = self
-                .index_helper
-                .compute_index(current_selection.x, current_selection.y);
-
-            if self.mine_states[index] != MineState::Revealed {
-                if is_right_button || is_eraser {
-                    let state = self.mine_states[index].cycle();
-                    self.mine_states[index] = state;
-                    self.ui.update_tile_with_state(&current_selection, state)?;
-                } else if self.mine_states[index] == MineState::Empty {
-                    if self.sweep(current_selection.x, current_selection.y)? {
-                        // We hit a mine! Setup and play an animation while locking any input.
-                        let hit_x = current_selection.x;
-                        let hit_y = current_selection.y;
-
-                        // First, hide the selection visual and reset the selection
-                        self.ui.select_tile(None)?;
-
-                        self.play_animation_on_all_mines(hit_x, hit_y)?;
-
-                        self.game_over = true;
-                    } else if self.check_if_won() {
-                        self.ui.select_tile(None)?;
-                        // TODO: Play a win animation
-                        self.game_over = true;
-                    }
-                }
+            if is_right_button || is_eraser {
+                self.cycle_mine_state(current_selection)?;
+            } else {
+                self.reveal(current_selection)?;
             }
         } else {
             if is_right_button || is_eraser {
@@ -193,380 +143,651 @@ impl Minesweeper {
         Ok(())
     }
 
+    /// Carries out a command from the keyboard. The commands that act on a
+    /// tile use the selected one, or the last one the pointer was over.
+    pub fn on_command(&mut self, command: Command) -> Result<()> {
+        match command {
+            // Undoing the move that ended the game also stops its animation.
+            Command::Undo => return self.undo(),
+            Command::Redo => return self.redo(),
+            // These are up to the window.
+            Command::ExportBoard | Command::Quit => return Ok(()),
+            _ => {}
+        }
+        // Input stays locked until the animation at the end of the game is over.
+        if self.ui.is_animation_playing() {
+            return Ok(());
+        }
+        if command == Command::NewGame {
+            return self.new_game(self.board.config());
+        }
+        if self.board.is_game_over() {
+            // Like a click, anything but moving the selection starts over.
+            return match command {
+                Command::MoveSelection(_) => Ok(()),
+                _ => self.new_game(self.board.config()),
+            };
+        }
+
+        let selection = self.ui.current_selected_tile().or(self.last_tile);
+        match (command, selection) {
+            (Command::MoveSelection(direction), _) => self.move_selection(direction)?,
+            (Command::Reveal, Some(tile)) => self.reveal(tile)?,
+            (Command::CycleMark, Some(tile)) => self.cycle_mine_state(tile)?,
+            (Command::Chord, Some(tile)) => self.chord(tile)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Starts from the middle of the board if nothing was selected yet.
+    fn move_selection(&mut self, direction: Direction) -> Result<()> {
+        let config = self.board.config();
+        let tile = self
+            .ui
+            .current_selected_tile()
+            .or(self.last_tile)
+            .map(|tile| {
+                let (dx, dy) = direction.offset();
+                let (x, y) = (tile.x + dx, tile.y + dy);
+                if self.board.topology().wraps {
+                    TileCoordinate {
+                        x: x.rem_euclid(config.width()),
+                        y: y.rem_euclid(config.height()),
+                    }
+                } else {
+                    TileCoordinate {
+                        x: x.clamp(0, config.width() - 1),
+                        y: y.clamp(0, config.height() - 1),
+                    }
+                }
+            })
+            .unwrap_or(TileCoordinate {
+                x: config.width() / 2,
+                y: config.height() / 2,
+            });
+
+        self.last_tile = Some(tile);
+        self.ui.select_tile(Some(tile))
+    }
+
     pub fn check_and_clear_satisfied(&mut self) -> Result<()> {
         // OK, we're outside of the unrevealed/flagged/etc tiles, but we SHOULD be at last_tile
+        if let Some(cur_tile) = self.last_tile {
+            self.chord(cur_tile)?;
+        }
+
+        Ok(())
+    }
+
+    // The moves below are shared by the pointer, the keyboard and replays,
+    // which all end up on a tile in different ways. Every click counts towards
+    // the stats, even the ones that don't change anything.
 
-        if self.last_tile.is_none() {
-            // If OOB, do nothing
+    fn reveal(&mut self, tile: TileCoordinate) -> Result<()> {
+        self.stopwatch.start();
+        self.record(ReplayAction::Reveal(tile));
+        self.played_own_game |= self.is_own_game();
+        self.clicks.left += 1;
+        if self.board.mine_state(&tile) != MineState::Empty {
             return Ok(());
         }
+        let had_mines = self.board.has_mines();
+        let outcome = self.board.sweep(&mut self.ui, &tile)?;
+        if !had_mines && self.board.has_mines() {
+            self.emit(GameEvent::MinesGenerated);
+        }
+        self.emit_revealed_tiles();
+        self.on_move_completed(outcome)
+    }
 
-        let cur_tile = self
-            .last_tile
-            .expect("Somehow last tile became None after test");
-
-        // Does the current tile have a number in it?
-        let index = self.index_helper.compute_index(cur_tile.x, cur_tile.y);
-        if self.neighbor_counts[index] < 1 || self.mine_states[index] != MineState::Revealed {
-            // No neighbors, or not revealed, do nothing!
+    fn cycle_mine_state(&mut self, tile: TileCoordinate) -> Result<()> {
+        self.stopwatch.start();
+        self.record(ReplayAction::CycleMineState(tile));
+        self.played_own_game |= self.is_own_game();
+        self.clicks.right += 1;
+        if self.board.mine_state(&tile) == MineState::Revealed {
             return Ok(());
         }
+        let before = self.board.mine_state(&tile);
+        self.board.cycle_mine_state(&mut self.ui, &tile)?;
+        let state = self.board.mine_state(&tile);
+        if state != before {
+            self.emit(GameEvent::TileMarked { tile, state });
+        }
+        Ok(())
+    }
 
-        // Make a vector of coordinates to query based on current coordinate
-        let base_vec: Vec<(i32, i32)> = vec![
-            (cur_tile.x - 1, cur_tile.y - 1),
-            (cur_tile.x, cur_tile.y - 1),
-            (cur_tile.x + 1, cur_tile.y - 1),
-            (cur_tile.x - 1, cur_tile.y),
-            (cur_tile.x + 1, cur_tile.y),
-            (cur_tile.x - 1, cur_tile.y + 1),
-            (cur_tile.x, cur_tile.y + 1),
-            (cur_tile.x + 1, cur_tile.y + 1),
-        ];
-
-        // Filter out-of-bounds if we're on the edges
-        let width = self.game_board_width;
-        let height = self.game_board_height;
-        let query_vec: Vec<(i32, i32)> = base_vec
-            .into_iter()
-            .filter(|cur| cur.0 >= 0 && cur.0 < width && cur.1 >= 0 && cur.1 < height)
-            .collect();
-        // See if all mines are marked that are in those 8 (or fewer) tiles
-        let mut flag_count = 0;
-        for query_coord in &query_vec {
-            let query_index = self
-                .index_helper
-                .compute_index(query_coord.0, query_coord.1);
-            if self.mine_states[query_index] == MineState::Flag {
-                flag_count += 1;
-            }
+    fn chord(&mut self, tile: TileCoordinate) -> Result<()> {
+        self.stopwatch.start();
+        self.record(ReplayAction::Chord(tile));
+        self.played_own_game |= self.is_own_game();
+        self.clicks.chord += 1;
+        let outcome = self.board.chord(&mut self.ui, &tile)?;
+        if !self.board.last_revealed().is_empty() {
+            self.emit(GameEvent::ChordPerformed { tile });
+            self.emit_revealed_tiles();
         }
-        if flag_count != self.neighbor_counts[index] {
-            // Too many or not enough flags
-            return Ok(());
+        self.on_move_completed(outcome)
+    }
+
+    fn emit_revealed_tiles(&self) {
+        for tile in self.board.last_revealed() {
+            self.emit(GameEvent::TileRevealed { tile: *tile });
         }
+    }
 
-        // OK, go through the query_vec and try and reveal all of them with sweep if they're not flagged
-        let mut hit_coordinate: Option<TileCoordinate> = None;
-        for query_coord in &query_vec {
-            let query_index = self
-                .index_helper
-                .compute_index(query_coord.0, query_coord.1);
-            // Is it unrevealed?  Only click on those spaces
-            if self.mine_states[query_index] != MineState::Empty {
-                // Already revealed, so don't click
-                continue;
-            }
-            if self.sweep(query_coord.0, query_coord.1)? {
-                hit_coordinate = Some(TileCoordinate {
-                    x: query_coord.0,
-                    y: query_coord.1,
-                });
-                break;
+    fn on_move_completed(&mut self, outcome: MoveOutcome) -> Result<()> {
+        if outcome != MoveOutcome::Continue {
+            self.update_game_stats(outcome);
+            // Undo makes times meaningless, and replays were already counted when they were played.
+            // Records are kept per board size and rules, which is only comparable between standard boards.
+            if !self.board.is_practice_mode() && !self.is_replaying() && self.is_standard_board() {
+                let rules = self.record_rules();
+                self.finished_record = self
+                    .game_stats
+                    .map(|stats| GameRecord::new(self.board.config(), rules, stats));
             }
+            self.finish_recording();
         }
 
-        if let Some(cur_coordinate) = hit_coordinate {
-            // We hit a mine! Setup and play an animation while locking any input.
-            let hit_x = cur_coordinate.x;
-            let hit_y = cur_coordinate.y;
+        match outcome {
+            MoveOutcome::Continue => {}
+            MoveOutcome::Lost(tile) => self.emit(GameEvent::GameLost {
+                tile,
+                stats: self.game_stats,
+            }),
+            MoveOutcome::Won => self.emit(GameEvent::GameWon {
+                stats: self.game_stats,
+            }),
+        }
+        self.show_outcome(outcome)
+    }
 
-            // First, hide the selection visual and reset the selection
-            self.ui.select_tile(None)?;
+    fn update_game_stats(&mut self, outcome: MoveOutcome) {
+        self.stopwatch.stop();
+        self.game_stats = self
+            .board
+            .game_stats(outcome, self.stopwatch.elapsed(), self.clicks);
+    }
+
+    // Plays the animation at the end of a game, if the move ended it.
+    fn show_outcome(&mut self, outcome: MoveOutcome) -> Result<()> {
+        match outcome {
+            MoveOutcome::Continue => {}
+            MoveOutcome::Lost(hit_tile) => {
+                // We hit a mine! Setup and play an animation while locking any input.
+                // First, hide the selection visual and reset the selection
+                self.ui.select_tile(None)?;
+
+                // Show where the game went wrong before the mines start moving.
+                for (tile, reveal) in self.board.loss_reveal(&hit_tile) {
+                    self.ui.show_loss_reveal(&tile, reveal)?;
+                }
+                self.play_animation_on_all_mines(&hit_tile)?;
+            }
+            MoveOutcome::Won => {
+                // Flag whatever mines are left and send a wave over the board, while locking any input.
+                self.ui.select_tile(None)?;
 
-            self.play_animation_on_all_mines(hit_x, hit_y)?;
+                self.play_win_animation()?;
+            }
+        }
+        Ok(())
+    }
 
-            self.game_over = true;
-        } else if self.check_if_won() {
-            self.ui.select_tile(None)?;
-            // TODO: Play a win animation
-            self.game_over = true;
+    pub fn undo(&mut self) -> Result<()> {
+        let was_game_over = self.board.is_game_over();
+        let had_mines = self.board.has_mines();
+        if !self.board.undo(&mut self.ui)? {
+            return Ok(());
         }
+        self.record(ReplayAction::Undo);
+        for event in events::undo_events(&self.board) {
+            self.emit(event);
+        }
+        if had_mines && !self.board.has_mines() {
+            // The next sweep places different mines, and a replay only keeps
+            // the last ones, so this game can't be played back anymore.
+            self.recorder = None;
+        }
+        if was_game_over {
+            self.game_stats = None;
+            // The animations at the end of the game change the whole board, so put it back.
+            self.ui.stop_animations()?;
+            self.board.redraw(&mut self.ui)?;
+        }
+        Ok(())
+    }
 
+    pub fn redo(&mut self) -> Result<()> {
+        let had_mines = self.board.has_mines();
+        if let Some(outcome) = self.board.redo(&mut self.ui)? {
+            self.record(ReplayAction::Redo);
+            if !had_mines && self.board.has_mines() {
+                self.emit(GameEvent::MinesGenerated);
+            }
+            for event in events::redo_events(&self.board) {
+                self.emit(event);
+            }
+            // The game already ended this way once, so it isn't announced or
+            // stored again.
+            if outcome != MoveOutcome::Continue {
+                self.update_game_stats(outcome);
+            }
+            self.show_outcome(outcome)?;
+        }
         Ok(())
     }
 
-    fn new_game(&mut self, board_width: i32, board_height: i32, mines: i32) -> Result<()> {
-        self.game_board_width = board_width;
-        self.game_board_height = board_height;
-        self.index_helper = IndexHelper::new(board_width, board_height);
+    pub fn set_practice_mode(&mut self, practice_mode: bool) {
+        self.restore_own_rules();
+        self.board.set_practice_mode(practice_mode);
+    }
 
-        self.ui.reset(&SizeInt32 {
-            Width: board_width,
-            Height: board_height,
-        })?;
+    /// Whether the player has made a move of their own, rather than only
+    /// watching a replay or playing a board loaded from a file. Until then the
+    /// save on disk isn't this session's to replace.
+    pub fn has_played_own_game(&self) -> bool {
+        self.played_own_game
+    }
 
-        for mine_state in self.mine_states.iter_mut() {
-            *mine_state = MineState::Empty
+    fn is_own_game(&self) -> bool {
+        !self.is_replaying() && !self.playing_loaded_board
+    }
+
+    /// The game to write to disk, or `None` if there's nothing worth resuming.
+    pub fn saved_game(&self) -> Option<SavedGame> {
+        if self.board.is_game_over() || self.board.is_untouched() || self.is_replaying() {
+            return None;
         }
 
-        self.game_over = false;
-        self.mine_generation_state = MineGenerationState::Deferred;
-        self.num_mines = mines;
+        Some(SavedGame {
+            board: self.board.snapshot(),
+            elapsed: self.stopwatch.elapsed(),
+            clicks: self.clicks,
+        })
+    }
+
+    pub fn resume(&mut self, saved: SavedGame) -> Result<()> {
+        let mut board = Board::from_snapshot(saved.board).map_err(invalid_arg)?;
+        board.set_practice_mode(self.board.is_practice_mode());
+        self.board = board;
+
+        self.ui.reset(
+            &board_size_in_tiles(&self.board.config()),
+            self.board.topology(),
+        )?;
+        self.board.redraw(&mut self.ui)?;
+
         self.last_tile = None;
+        self.stopwatch = Stopwatch::with_elapsed(saved.elapsed);
+        self.clicks = saved.clicks;
+        self.game_stats = None;
+        self.played_own_game = true;
+        self.playing_loaded_board = false;
+        // A replay has to start from an empty board.
+        self.recorder = None;
+
+        self.emit(GameEvent::GameStarted {
+            config: self.board.config(),
+            seed: self.board.seed(),
+        });
+        Ok(())
+    }
 
+    /// Starts a new game on a board that was made elsewhere, instead of
+    /// generating one.
+    pub fn load_board(&mut self, board: MbfBoard) -> Result<()> {
+        // The format only knows about square tiles with edges and a single
+        // mine each.
+        self.set_own_rules_aside();
+        self.board.set_topology(Topology::default());
+        self.board.set_max_mines_per_tile(1);
+        self.start_game(board.config, rand::random())?;
+        self.preset_mines(board.mines.into_iter().map(i32::from).collect())?;
+        self.playing_loaded_board = true;
         Ok(())
     }
 
-    fn sweep(&mut self, x: i32, y: i32) -> Result<bool> {
-        if self.mine_generation_state == MineGenerationState::Deferred {
-            // We don't want the first thing that the user clicks to be a mine.
-            // Generate mines but avoid putting it where the user clicked.
-            self.generate_mines(self.num_mines, x, y);
-            self.mine_generation_state = MineGenerationState::Generated;
+    /// The layout of the last game, once it's over.
+    pub fn mbf_board(&self) -> Option<MbfBoard> {
+        if !self.board.is_game_over() || !self.is_standard_board() {
+            return None;
         }
 
-        let mut hit_mine = false;
-        let mut sweeps: VecDeque<usize> = VecDeque::new();
-        sweeps.push_back(self.index_helper.compute_index(x, y));
-        self.reveal(*sweeps.front().unwrap())?;
+        let snapshot = self.board.snapshot();
+        Some(MbfBoard {
+            config: snapshot.config,
+            mines: snapshot.mines?.into_iter().map(|mines| mines > 0).collect(),
+        })
+    }
 
-        while !sweeps.is_empty() {
-            let index = *sweeps.front().unwrap();
-            let current_x = self.index_helper.compute_x_from_index(index);
-            let current_y = self.index_helper.compute_y_from_index(index);
+    // Square tiles with edges and a single mine each, like every other
+    // Minesweeper has.
+    fn is_standard_board(&self) -> bool {
+        self.board.topology() == Topology::default() && self.board.max_mines_per_tile() == 1
+    }
 
-            if self.mines[index] {
-                // We hit a mine, game over
-                hit_mine = true;
-                break;
-            }
+    fn preset_mines(&mut self, mines: Vec<i32>) -> Result<()> {
+        self.board.preset_mines(mines).map_err(invalid_arg)?;
+        self.emit(GameEvent::MinesGenerated);
+        Ok(())
+    }
 
-            if self.neighbor_counts[index] == 0 {
-                self.push_if_unmarked(&mut sweeps, current_x - 1, current_y - 1)?;
-                self.push_if_unmarked(&mut sweeps, current_x, current_y - 1)?;
-                self.push_if_unmarked(&mut sweeps, current_x + 1, current_y - 1)?;
-                self.push_if_unmarked(&mut sweeps, current_x + 1, current_y)?;
-                self.push_if_unmarked(&mut sweeps, current_x + 1, current_y + 1)?;
-                self.push_if_unmarked(&mut sweeps, current_x, current_y + 1)?;
-                self.push_if_unmarked(&mut sweeps, current_x - 1, current_y + 1)?;
-                self.push_if_unmarked(&mut sweeps, current_x - 1, current_y)?;
-            }
+    /// Plays back a recorded game. `advance_replay` has to be called
+    /// regularly until the replay is finished.
+    pub fn play_replay(&mut self, replay: Replay, speed: f64) -> Result<()> {
+        let settings = replay.settings;
+        self.set_own_rules_aside();
+        self.board.set_topology(settings.topology);
+        self.board
+            .set_max_mines_per_tile(settings.max_mines_per_tile);
+        self.board.set_lives(settings.lives);
+        self.board
+            .set_first_click_policy(settings.first_click_policy);
+        self.board.set_generation_mode(settings.generation_mode);
+        self.start_game(settings.config, settings.seed)?;
+
+        if let Some(mines) = replay.mines {
+            self.preset_mines(mines)?;
+        }
 
-            sweeps.pop_front().unwrap();
+        // Undo is only available in practice mode, so a replay that uses it needs it too.
+        let uses_history = replay
+            .events
+            .iter()
+            .any(|event| matches!(event.action, ReplayAction::Undo | ReplayAction::Redo));
+        if uses_history {
+            self.board.set_practice_mode(true);
         }
 
-        Ok(hit_mine)
+        self.recorder = None;
+        self.player = Some(ReplayPlayer::new(replay.events, speed));
+
+        Ok(())
     }
 
-    fn reveal(&mut self, index: usize) -> Result<()> {
-        let tile_coordinate = TileCoordinate {
-            x: self.index_helper.compute_x_from_index(index),
-            y: self.index_helper.compute_y_from_index(index),
+    /// Plays the events of the replay that are due. Returns whether the
+    /// replay is still playing.
+    pub fn advance_replay(&mut self) -> Result<bool> {
+        let actions = match &mut self.player {
+            Some(player) => player.due_events(),
+            None => return Ok(false),
         };
 
-        if self.mines[index] {
-            self.ui.update_tile_as_mine(&tile_coordinate)?;
-        } else {
-            let count = self.neighbor_counts[index];
-            self.ui
-                .update_tile_with_mine_count(&tile_coordinate, count)?;
+        for action in actions {
+            match action {
+                ReplayAction::Resized { width, height } => {
+                    self.on_parent_size_changed(&Vector2::new(width, height))?
+                }
+                ReplayAction::PointerMoved { x, y } => {
+                    self.on_pointer_moved(&Vector2::new(x, y))?
+                }
+                // The moves go straight to their tile, since they could have
+                // been made from the keyboard.
+                ReplayAction::Reveal(tile) => self.reveal(tile)?,
+                ReplayAction::CycleMineState(tile) => self.cycle_mine_state(tile)?,
+                ReplayAction::Chord(tile) => self.chord(tile)?,
+                ReplayAction::Undo => self.undo()?,
+                ReplayAction::Redo => self.redo()?,
+            }
         }
 
-        self.mine_states[index] = MineState::Revealed;
-        Ok(())
+        if self
+            .player
+            .as_ref()
+            .is_some_and(|player| player.is_finished())
+        {
+            self.player = None;
+        }
+        Ok(self.player.is_some())
     }
 
-    fn is_in_bounds_and_unmarked(&self, x: i32, y: i32) -> bool {
-        let index = self.index_helper.compute_index(x, y);
-        self.index_helper.is_in_bounds(x, y) && self.mine_states[index] == MineState::Empty
+    pub fn is_replaying(&self) -> bool {
+        self.player.is_some()
     }
 
-    fn push_if_unmarked(&mut self, sweeps: &mut VecDeque<usize>, x: i32, y: i32) -> Result<()> {
-        if self.is_in_bounds_and_unmarked(x, y) {
-            let index = self.index_helper.compute_index(x, y);
-            self.reveal(index)?;
-            sweeps.push_back(index);
-        }
+    /// Hands out the recording of the last game that ended, if it hasn't
+    /// been taken yet.
+    pub fn take_finished_replay(&mut self) -> Option<Replay> {
+        self.finished_replay.take()
+    }
 
-        Ok(())
+    /// The rules the current game is kept in the records under.
+    pub fn record_rules(&self) -> RecordRules {
+        RecordRules::new(
+            self.board.first_click_policy(),
+            self.board.generation_mode(),
+            self.board.lives(),
+        )
+    }
+
+    /// Hands out the record of the last game that ended, if it counts
+    /// towards the records and hasn't been taken yet.
+    pub fn take_finished_record(&mut self) -> Option<GameRecord> {
+        self.finished_record.take()
+    }
+
+    /// Registers a listener for the events of every game from now on.
+    pub fn add_listener(&mut self, listener: impl FnMut(&GameEvent) + Send + 'static) {
+        self.listeners.lock().unwrap().add(listener);
+    }
+
+    fn emit(&self, event: GameEvent) {
+        self.listeners.lock().unwrap().emit(&event);
     }
 
-    fn generate_mines(&mut self, num_mines: i32, exclude_x: i32, exclude_y: i32) {
-        for mine in self.mines.iter_mut() {
-            *mine = false;
+    fn record(&mut self, action: ReplayAction) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(action);
         }
+    }
 
-        let between = Uniform::new(
-            0_usize,
-            (self.game_board_width * self.game_board_height) as usize,
-        )
-        .expect("Failed to create Uniform distribution");
-        let mut rng = rand::rng();
-        for _i in 0..num_mines {
-            let mut index: usize;
-            let exclude_index = self.index_helper.compute_index(exclude_x, exclude_y);
-            // do while loops look weird in rust...
-            while {
-                index = between.sample(&mut rng);
-                index == exclude_index || self.mines[index]
-            } {}
-
-            self.mines[index] = true;
-        }
-
-        self.neighbor_counts.clear();
-        for i in 0..self.mines.len() {
-            let x = self.index_helper.compute_x_from_index(i);
-            let y = self.index_helper.compute_y_from_index(i);
-
-            if self.mines[i] {
-                // -1 means a mine
-                self.neighbor_counts.push(-1);
-                // DEBUG
-                if cfg!(feature = "show-mines") {
-                    self.ui
-                        .update_tile_with_state(&TileCoordinate { x, y }, MineState::Question)
-                        .unwrap();
-                }
-            } else {
-                let count = self.get_surrounding_mine_count(x, y);
-                self.neighbor_counts.push(count);
-            }
+    fn finish_recording(&mut self) {
+        if let Some(recorder) = &self.recorder {
+            let snapshot = self.board.snapshot();
+            self.finished_replay = Some(Replay {
+                settings: BoardSettings {
+                    config: snapshot.config,
+                    seed: snapshot.seed,
+                    first_click_policy: snapshot.first_click_policy,
+                    generation_mode: snapshot.generation_mode,
+                    topology: snapshot.topology,
+                    max_mines_per_tile: snapshot.max_mines_per_tile,
+                    lives: snapshot.lives,
+                },
+                mines: snapshot.mines,
+                events: recorder.events().to_vec(),
+            });
         }
     }
 
-    fn test_spot(&self, x: i32, y: i32) -> bool {
-        self.index_helper.is_in_bounds(x, y) && self.mines[self.index_helper.compute_index(x, y)]
+    /// How the current game went, once it has ended.
+    pub fn game_stats(&self) -> Option<&GameStats> {
+        self.game_stats.as_ref()
     }
 
-    fn get_surrounding_mine_count(&self, x: i32, y: i32) -> i32 {
-        let mut count = 0;
+    pub fn config(&self) -> BoardConfig {
+        self.board.config()
+    }
 
-        if self.test_spot(x + 1, y) {
-            count += 1;
-        }
+    pub fn seed(&self) -> u64 {
+        self.board.seed()
+    }
 
-        if self.test_spot(x - 1, y) {
-            count += 1;
-        }
+    pub fn set_first_click_policy(&mut self, policy: FirstClickPolicy) {
+        self.restore_own_rules();
+        self.board.set_first_click_policy(policy);
+    }
 
-        if self.test_spot(x, y + 1) {
-            count += 1;
-        }
+    pub fn set_generation_mode(&mut self, mode: GenerationMode) {
+        self.restore_own_rules();
+        self.board.set_generation_mode(mode);
+    }
 
-        if self.test_spot(x, y - 1) {
-            count += 1;
-        }
+    /// Starts the current game over with tiles of another shape.
+    pub fn set_topology(&mut self, topology: Topology) -> Result<()> {
+        self.restore_own_rules();
+        self.board.set_topology(topology);
+        self.new_game_with_seed(self.board.config(), self.board.seed())
+    }
 
-        if self.test_spot(x + 1, y + 1) {
-            count += 1;
-        }
+    /// Starts the current game over with tiles that can hold up to this many
+    /// mines.
+    pub fn set_max_mines_per_tile(&mut self, max_mines_per_tile: i32) -> Result<()> {
+        self.restore_own_rules();
+        self.board.set_max_mines_per_tile(max_mines_per_tile);
+        self.new_game_with_seed(self.board.config(), self.board.seed())
+    }
 
-        if self.test_spot(x - 1, y - 1) {
-            count += 1;
-        }
+    pub fn lives(&self) -> i32 {
+        self.board.lives()
+    }
 
-        if self.test_spot(x - 1, y + 1) {
-            count += 1;
-        }
+    pub fn lives_left(&self) -> i32 {
+        self.board.lives_left()
+    }
 
-        if self.test_spot(x + 1, y - 1) {
-            count += 1;
-        }
+    pub fn set_lives(&mut self, lives: i32) {
+        self.restore_own_rules();
+        self.board.set_lives(lives);
+    }
 
-        count
+    pub fn new_game(&mut self, config: BoardConfig) -> Result<()> {
+        self.new_game_with_seed(config, rand::random())
     }
 
-    fn check_tile_for_mine_for_animation(
-        &self,
-        x: i32,
-        y: i32,
-        mine_indices: &mut VecDeque<usize>,
-        visited_tiles: &mut i32,
-        mines_in_ring: &mut i32,
-    ) {
-        if self.index_helper.is_in_bounds(x, y) {
-            let tile_index = self.index_helper.compute_index(x, y);
-            if self.mines[tile_index] {
-                mine_indices.push_back(tile_index);
-                *mines_in_ring += 1;
-            }
-            *visited_tiles += 1;
+    pub fn new_game_with_seed(&mut self, config: BoardConfig, seed: u64) -> Result<()> {
+        self.restore_own_rules();
+        self.start_game(config, seed)
+    }
+
+    // Loaded boards and replays bring rules of their own, which only last for
+    // that game.
+    fn set_own_rules_aside(&mut self) {
+        if self.own_rules.is_none() {
+            self.own_rules = Some(OwnRules::of(&self.board));
         }
     }
 
-    fn play_animation_on_all_mines(&mut self, center_x: i32, center_y: i32) -> Result<()> {
-        // Build a queue that contains the indices of the mines in a spiral starting from the clicked mine.
-        let mut mine_indices: VecDeque<usize> = VecDeque::new();
-        let mut mines_per_ring: VecDeque<i32> = VecDeque::new();
-        let mut visited_tiles: i32 = 0;
-        let mut ring_level: i32 = 0;
-        while visited_tiles < (self.game_board_width * self.game_board_height) {
-            if ring_level == 0 {
-                let hit_mine_index = self.index_helper.compute_index(center_x, center_y);
-                mine_indices.push_back(hit_mine_index);
-                mines_per_ring.push_back(1);
-                visited_tiles += 1;
-            } else {
-                let mut current_mines_in_ring = 0;
-
-                // Check the top side
-                for x in (center_x - ring_level)..=(center_x + ring_level) {
-                    let y = center_y - ring_level;
-                    self.check_tile_for_mine_for_animation(
-                        x,
-                        y,
-                        &mut mine_indices,
-                        &mut visited_tiles,
-                        &mut current_mines_in_ring,
-                    );
-                }
+    fn restore_own_rules(&mut self) {
+        if let Some(rules) = self.own_rules.take() {
+            rules.apply_to(&mut self.board);
+        }
+    }
 
-                // Check the right side
-                for y in (center_y - ring_level + 1)..=(center_y + ring_level) {
-                    let x = center_x + ring_level;
-                    self.check_tile_for_mine_for_animation(
-                        x,
-                        y,
-                        &mut mine_indices,
-                        &mut visited_tiles,
-                        &mut current_mines_in_ring,
-                    );
-                }
+    fn start_game(&mut self, config: BoardConfig, seed: u64) -> Result<()> {
+        self.board.reset(config, seed);
+        self.ui
+            .reset(&board_size_in_tiles(&config), self.board.topology())?;
 
-                // Check the bottom side
-                for x in (center_x - ring_level)..(center_x + ring_level) {
-                    let y = center_y + ring_level;
-                    self.check_tile_for_mine_for_animation(
-                        x,
-                        y,
-                        &mut mine_indices,
-                        &mut visited_tiles,
-                        &mut current_mines_in_ring,
-                    );
-                }
+        self.last_tile = None;
+        self.stopwatch = Stopwatch::default();
+        self.clicks = ClickCounts::default();
+        self.game_stats = None;
+        self.playing_loaded_board = false;
+
+        // Every game is recorded from the size of the board it started with.
+        let mut recorder = ReplayRecorder::new();
+        recorder.record(ReplayAction::Resized {
+            width: self.parent_size.X,
+            height: self.parent_size.Y,
+        });
+        self.recorder = Some(recorder);
+
+        self.emit(GameEvent::GameStarted { config, seed });
+        Ok(())
+    }
 
-                // Check the left side
-                for y in (center_y - ring_level + 1)..(center_y + ring_level) {
-                    let x = center_x - ring_level;
-                    self.check_tile_for_mine_for_animation(
-                        x,
-                        y,
-                        &mut mine_indices,
-                        &mut visited_tiles,
-                        &mut current_mines_in_ring,
-                    );
-                }
+    fn play_animation_on_all_mines(&mut self, center: &TileCoordinate) -> Result<()> {
+        // Build a queue that contains the indices of the mines in a spiral starting from the clicked mine.
+        let (mine_indices, mines_per_ring) = self.board.mines_in_spiral_order(center);
 
-                if current_mines_in_ring > 0 {
-                    mines_per_ring.push_back(current_mines_in_ring);
-                }
-            }
-            ring_level += 1;
+        // Iterate and animate each mine
+        let on_completed = self.animation_completed_callback();
+        self.ui
+            .play_mine_animations(mine_indices, mines_per_ring, on_completed)?;
+
+        Ok(())
+    }
+
+    fn play_win_animation(&mut self) -> Result<()> {
+        for (tile, mines) in self.board.hidden_mines() {
+            self.ui
+                .update_tile_with_state(&tile, MineState::Flag(mines))?;
         }
 
-        // Iterate and animate each mine
-        self.ui.play_mine_animations(mine_indices, mines_per_ring)?;
+        // The wave starts from the last tile the player pointed at, which is
+        // where the game was won unless it was through a redo.
+        let config = self.board.config();
+        let center = self.last_tile.unwrap_or(TileCoordinate {
+            x: config.width() / 2,
+            y: config.height() / 2,
+        });
+        let (tile_indices, tiles_per_ring) = self.board.tiles_in_spiral_order(&center);
+
+        let on_completed = self.animation_completed_callback();
+        self.ui
+            .play_win_animations(tile_indices, tiles_per_ring, on_completed)?;
 
         Ok(())
     }
 
-    fn check_if_won(&self) -> bool {
-        self.mine_states
-            .iter()
-            .filter(|state| **state != MineState::Revealed)
-            .count()
-            == self.num_mines as usize
+    // Lets the listeners know once the UI is done animating, which happens
+    // outside of any call into the game.
+    fn animation_completed_callback(&self) -> impl Fn() + Send + 'static {
+        let listeners = self.listeners.clone();
+        move || {
+            listeners
+                .lock()
+                .unwrap()
+                .emit(&GameEvent::AnimationCompleted)
+        }
     }
 }
+
+// The rules of a game that the player picks, as opposed to the board itself.
+#[derive(Copy, Clone)]
+struct OwnRules {
+    first_click_policy: FirstClickPolicy,
+    generation_mode: GenerationMode,
+    topology: Topology,
+    max_mines_per_tile: i32,
+    lives: i32,
+    practice_mode: bool,
+}
+
+impl OwnRules {
+    fn of(board: &Board) -> Self {
+        Self {
+            first_click_policy: board.first_click_policy(),
+            generation_mode: board.generation_mode(),
+            topology: board.topology(),
+            max_mines_per_tile: board.max_mines_per_tile(),
+            lives: board.lives(),
+            practice_mode: board.is_practice_mode(),
+        }
+    }
+
+    fn apply_to(self, board: &mut Board) {
+        board.set_first_click_policy(self.first_click_policy);
+        board.set_generation_mode(self.generation_mode);
+        board.set_topology(self.topology);
+        board.set_max_mines_per_tile(self.max_mines_per_tile);
+        board.set_lives(self.lives);
+        board.set_practice_mode(self.practice_mode);
+    }
+}
+
+fn board_size_in_tiles(config: &BoardConfig) -> SizeInt32 {
+    SizeInt32 {
+        Width: config.width(),
+        Height: config.height(),
+    }
+}
+
+/// Reports an error from the game's files or settings as an invalid argument.
+pub fn invalid_arg(error: impl std::fmt::Display) -> Error {
+    Error::new(E_INVALIDARG, error.to_string())
+}
@@ -1,3 +1,4 @@
+use minesweeper_rs::layout::Point;
 use windows_numerics::{Vector2, Vector3};
 
 pub trait FromVector2 {
@@ -11,3 +12,23 @@ impl FromVector2 for Vector3 {
         Vector3::new(value.X, value.Y, z)
     }
 }
+
+pub trait FromPoint {
+    fn from_point(value: Point) -> Self;
+}
+
+impl FromPoint for Vector2 {
+    fn from_point(value: Point) -> Self {
+        Vector2::new(value.x, value.y)
+    }
+}
+
+pub trait ToPoint {
+    fn to_point(&self) -> Point;
+}
+
+impl ToPoint for Vector2 {
+    fn to_point(&self) -> Point {
+        Point::new(self.X, self.Y)
+    }
+}
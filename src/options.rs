@@ -0,0 +1,215 @@
+use crate::board::{
+    BoardConfig, BoardConfigError, FirstClickPolicy, GenerationMode, MineGenerationError,
+};
+use crate::topology::{NeighborhoodError, TileShape, Topology};
+use std::fmt;
+use std::path::PathBuf;
+
+/// How to start the game, as given on the command line.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Options {
+    pub config: BoardConfig,
+    pub seed: Option<u64>,
+    pub first_click_policy: FirstClickPolicy,
+    pub generation_mode: GenerationMode,
+    pub topology: Topology,
+    pub max_mines_per_tile: i32,
+    pub lives: i32,
+    pub practice_mode: bool,
+    pub replay: Option<PathBuf>,
+    pub replay_speed: f64,
+    pub mbf: Option<PathBuf>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum OptionsError {
+    MissingValue { option: String },
+    InvalidValue { option: String, value: String },
+    UnexpectedArguments(Vec<String>),
+    InvalidBoard(BoardConfigError),
+    InvalidNeighborhood(NeighborhoodError),
+    UnsupportedTopology,
+    IncompatibleOptions { first: String, second: String },
+    NoRoomForOpening { num_mines: i32, max_mines: usize },
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionsError::MissingValue { option } => write!(f, "{} needs a value", option),
+            OptionsError::InvalidValue { option, value } => {
+                write!(f, "{} is not a valid value for {}", value, option)
+            }
+            OptionsError::UnexpectedArguments(arguments) => write!(
+                f,
+                "expected a preset or \"<width> <height> <mines>\", found \"{}\"",
+                arguments.join(" ")
+            ),
+            OptionsError::InvalidBoard(error) => write!(f, "{}", error),
+            OptionsError::InvalidNeighborhood(error) => write!(f, "{}", error),
+            OptionsError::UnsupportedTopology => write!(
+                f,
+                "hex boards can only wrap around with an even number of rows, and can't have custom neighbors"
+            ),
+            OptionsError::IncompatibleOptions { first, second } => {
+                write!(f, "{} can't be combined with {}", first, second)
+            }
+            OptionsError::NoRoomForOpening {
+                num_mines,
+                max_mines,
+            } => write!(
+                f,
+                "{} mines don't fit around a safe first opening, the board has room for {}",
+                num_mines, max_mines
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+impl From<BoardConfigError> for OptionsError {
+    fn from(error: BoardConfigError) -> Self {
+        OptionsError::InvalidBoard(error)
+    }
+}
+
+impl From<NeighborhoodError> for OptionsError {
+    fn from(error: NeighborhoodError) -> Self {
+        OptionsError::InvalidNeighborhood(error)
+    }
+}
+
+impl Options {
+    /// Accepts either the name of a preset or a custom "<width> <height> <mines>"
+    /// board, optionally followed by "--seed <seed>",
+    /// "--first-click <unprotected|safe|opening>", "--no-guess", "--hex",
+    /// "--wrap", "--neighbors <orthogonal|knight|radius-2|radius-3|<x>,<y> ...>",
+    /// "--mines-per-tile <1-9>", "--lives <count>" and "--practice".
+    /// "--replay <file>" plays back a recorded game instead, optionally sped up
+    /// with "--replay-speed <factor>", and "--mbf <file>" starts on a board
+    /// exported from another Minesweeper.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, OptionsError> {
+        let mut positional = Vec::new();
+        let mut seed = None;
+        let mut first_click_policy = FirstClickPolicy::default();
+        let mut generation_mode = GenerationMode::default();
+        let mut topology = Topology::default();
+        let mut max_mines_per_tile = 1;
+        let mut lives = 1;
+        let mut practice_mode = false;
+        let mut replay = None;
+        let mut replay_speed: f64 = 1.0;
+        let mut mbf = None;
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next().ok_or_else(|| OptionsError::MissingValue {
+                    option: arg.clone(),
+                })
+            };
+            let invalid = |value: String| OptionsError::InvalidValue {
+                option: arg.clone(),
+                value,
+            };
+
+            if arg == "--seed" {
+                let value = value()?;
+                seed = Some(value.parse().map_err(|_| invalid(value))?);
+            } else if arg == "--first-click" {
+                first_click_policy = match value()?.as_str() {
+                    "unprotected" => FirstClickPolicy::Unprotected,
+                    "safe" => FirstClickPolicy::SafeTile,
+                    "opening" => FirstClickPolicy::SafeOpening,
+                    other => return Err(invalid(other.to_owned())),
+                };
+            } else if arg == "--no-guess" {
+                generation_mode = GenerationMode::NoGuess {
+                    max_attempts: GenerationMode::DEFAULT_NO_GUESS_ATTEMPTS,
+                };
+            } else if arg == "--hex" {
+                topology.shape = TileShape::Hex;
+            } else if arg == "--wrap" {
+                topology.wraps = true;
+            } else if arg == "--neighbors" {
+                topology.neighborhood = Some(value()?.parse()?);
+            } else if arg == "--mines-per-tile" {
+                let value = value()?;
+                max_mines_per_tile = match value.parse() {
+                    Ok(max) if (1..=BoardConfig::MAX_MINES_PER_TILE).contains(&max) => max,
+                    _ => return Err(invalid(value)),
+                };
+            } else if arg == "--lives" {
+                let value = value()?;
+                lives = match value.parse() {
+                    Ok(count) if count >= 1 => count,
+                    _ => return Err(invalid(value)),
+                };
+            } else if arg == "--practice" {
+                practice_mode = true;
+            } else if arg == "--replay" {
+                replay = Some(PathBuf::from(value()?));
+            } else if arg == "--mbf" {
+                mbf = Some(PathBuf::from(value()?));
+            } else if arg == "--replay-speed" {
+                let value = value()?;
+                replay_speed = match value.parse::<f64>() {
+                    Ok(speed) if speed.is_finite() && speed > 0.0 => speed,
+                    _ => return Err(invalid(value)),
+                };
+            } else {
+                positional.push(arg);
+            }
+        }
+
+        let config = match positional.as_slice() {
+            [] => BoardConfig::default(),
+            [preset] if preset == "beginner" => BoardConfig::BEGINNER,
+            [preset] if preset == "intermediate" => BoardConfig::INTERMEDIATE,
+            [preset] if preset == "expert" => BoardConfig::EXPERT,
+            [width, height, mines] => {
+                let parse = |value: &str| -> Result<i32, OptionsError> {
+                    value
+                        .parse()
+                        .map_err(|_| OptionsError::UnexpectedArguments(positional.clone()))
+                };
+                BoardConfig::custom(parse(width)?, parse(height)?, parse(mines)?)?
+            }
+            _ => return Err(OptionsError::UnexpectedArguments(positional)),
+        };
+        if !topology.fits(&config) {
+            return Err(OptionsError::UnsupportedTopology);
+        }
+        if let Err(MineGenerationError::NotEnoughRoom {
+            num_mines,
+            available_tiles,
+            max_mines_per_tile,
+        }) = first_click_policy.check_room(&config, &topology, max_mines_per_tile)
+        {
+            return Err(OptionsError::NoRoomForOpening {
+                num_mines,
+                max_mines: available_tiles * max_mines_per_tile as usize,
+            });
+        }
+        // The solver behind no-guess boards assumes a single mine per tile.
+        if max_mines_per_tile > 1 && generation_mode != GenerationMode::Random {
+            return Err(OptionsError::IncompatibleOptions {
+                first: "--no-guess".to_owned(),
+                second: "--mines-per-tile".to_owned(),
+            });
+        }
+
+        Ok(Self {
+            config,
+            seed,
+            first_click_policy,
+            generation_mode,
+            topology,
+            max_mines_per_tile,
+            lives,
+            practice_mode,
+            replay,
+            replay_speed,
+            mbf,
+        })
+    }
+}
@@ -0,0 +1,411 @@
+use crate::board::{BoardConfig, FirstClickPolicy, GenerationMode};
+use crate::file_format::{self, FileError};
+use crate::stats::{ClickCounts, GameStats, ThreeBv};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const RECORDS_VERSION: u32 = 1;
+const RECORDS_KIND: &str = "records";
+const RECORDS_FILE_NAME: &str = "records.txt";
+
+/// The rules a game was played by that make it easier or harder. Games are
+/// only compared with games played by the same rules.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RecordRules {
+    pub first_click_policy: FirstClickPolicy,
+    pub no_guess: bool,
+    pub lives: i32,
+}
+
+impl Default for RecordRules {
+    fn default() -> Self {
+        Self {
+            first_click_policy: FirstClickPolicy::default(),
+            no_guess: false,
+            lives: 1,
+        }
+    }
+}
+
+impl RecordRules {
+    pub fn new(
+        first_click_policy: FirstClickPolicy,
+        generation_mode: GenerationMode,
+        lives: i32,
+    ) -> Self {
+        Self {
+            first_click_policy,
+            no_guess: generation_mode != GenerationMode::Random,
+            lives,
+        }
+    }
+}
+
+/// A game that was played to the end.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct GameRecord {
+    /// When the game ended, in milliseconds since the Unix epoch.
+    pub finished_at: u64,
+    pub config: BoardConfig,
+    pub rules: RecordRules,
+    pub stats: GameStats,
+}
+
+impl GameRecord {
+    pub fn new(config: BoardConfig, rules: RecordRules, stats: GameStats) -> Self {
+        let finished_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        Self {
+            finished_at,
+            config,
+            rules,
+            stats,
+        }
+    }
+
+    // One game per line:
+    // "game <finished at> <width> <height> <mines> <won|lost> <time ms>
+    //  <solved 3BV> <total 3BV> <left> <right> <chord> [<lives used>]
+    //  [first-click <unprotected|opening>] [no-guess] [lives <count>]"
+    // Everything after the clicks is left out when it's the default, like in
+    // games from before it existed.
+    fn line(&self) -> String {
+        let stats = &self.stats;
+        let mut line = format!(
+            "game {} {} {} {} {} {} {} {} {} {} {}",
+            self.finished_at,
+            self.config.width(),
+            self.config.height(),
+            self.config.num_mines(),
+            if stats.won { "won" } else { "lost" },
+            stats.time.as_millis(),
+            stats.three_bv.solved,
+            stats.three_bv.total,
+            stats.clicks.left,
+            stats.clicks.right,
+            stats.clicks.chord
+        );
+        if stats.lives_used > 0 {
+            line.push_str(&format!(" {}", stats.lives_used));
+        }
+        match self.rules.first_click_policy {
+            FirstClickPolicy::Unprotected => line.push_str(" first-click unprotected"),
+            FirstClickPolicy::SafeTile => {}
+            FirstClickPolicy::SafeOpening => line.push_str(" first-click opening"),
+        }
+        if self.rules.no_guess {
+            line.push_str(" no-guess");
+        }
+        if self.rules.lives != 1 {
+            line.push_str(&format!(" lives {}", self.rules.lives));
+        }
+        line
+    }
+
+    fn parse_line(line: &str) -> Option<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["game", finished_at, width, height, mines, result, time, solved, total, left, right, chord, rest @ ..] =>
+            {
+                let (lives_used, mut rest) = match rest {
+                    [lives_used, rest @ ..] if lives_used.parse::<u32>().is_ok() => {
+                        (lives_used.parse().ok()?, rest)
+                    }
+                    rest => (0, rest),
+                };
+                let mut rules = RecordRules::default();
+                loop {
+                    rest = match rest {
+                        [] => break,
+                        ["first-click", "unprotected", rest @ ..] => {
+                            rules.first_click_policy = FirstClickPolicy::Unprotected;
+                            rest
+                        }
+                        ["first-click", "opening", rest @ ..] => {
+                            rules.first_click_policy = FirstClickPolicy::SafeOpening;
+                            rest
+                        }
+                        ["no-guess", rest @ ..] => {
+                            rules.no_guess = true;
+                            rest
+                        }
+                        ["lives", lives, rest @ ..] => {
+                            rules.lives = lives.parse().ok().filter(|lives| *lives >= 1)?;
+                            rest
+                        }
+                        _ => return None,
+                    };
+                }
+
+                Some(Self {
+                    finished_at: finished_at.parse().ok()?,
+                    config: BoardConfig::custom(
+                        width.parse().ok()?,
+                        height.parse().ok()?,
+                        mines.parse().ok()?,
+                    )
+                    .ok()?,
+                    rules,
+                    stats: GameStats {
+                        won: match *result {
+                            "won" => true,
+                            "lost" => false,
+                            _ => return None,
+                        },
+                        time: Duration::from_millis(time.parse().ok()?),
+                        three_bv: ThreeBv {
+                            solved: solved.parse().ok()?,
+                            total: total.parse().ok()?,
+                        },
+                        clicks: ClickCounts {
+                            left: left.parse().ok()?,
+                            right: right.parse().ok()?,
+                            chord: chord.parse().ok()?,
+                        },
+                        lives_used,
+                    },
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The totals for one board configuration and set of rules.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct ConfigRecords {
+    pub played: u32,
+    pub won: u32,
+    pub current_streak: u32,
+    pub best_streak: u32,
+    /// The fastest win that didn't hit any mines.
+    pub best_time: Option<Duration>,
+}
+
+/// Every finished game, oldest first.
+///
+/// The file is a "minesweeper-rs records 1" line followed by one line per
+/// game, and games are only ever appended to it. Lines that can't be read
+/// are skipped, so a damaged file only loses the games on those lines.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Records {
+    history: Vec<GameRecord>,
+    skipped_lines: usize,
+}
+
+impl Records {
+    pub fn from_text(text: &str) -> Result<Self, FileError> {
+        let mut result = Self::default();
+        let header_prefix = format!("minesweeper-rs {} ", RECORDS_KIND);
+
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(version) = line.strip_prefix(&header_prefix) {
+                // Writing to a file we don't understand could damage it further.
+                if version.trim() != RECORDS_VERSION.to_string() {
+                    return Err(FileError::UnsupportedVersion(version.trim().to_owned()));
+                }
+                continue;
+            }
+
+            match GameRecord::parse_line(line) {
+                Some(record) => result.history.push(record),
+                None => result.skipped_lines += 1,
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub fn history(&self) -> &[GameRecord] {
+        &self.history
+    }
+
+    /// How many lines of the file couldn't be read.
+    pub fn skipped_lines(&self) -> usize {
+        self.skipped_lines
+    }
+
+    pub fn push(&mut self, record: GameRecord) {
+        self.history.push(record);
+    }
+
+    pub fn for_config(&self, config: &BoardConfig, rules: &RecordRules) -> ConfigRecords {
+        let mut result = ConfigRecords::default();
+        for record in self
+            .history
+            .iter()
+            .filter(|record| record.config == *config && record.rules == *rules)
+        {
+            result.played += 1;
+            if record.stats.won {
+                result.won += 1;
+                result.current_streak += 1;
+                result.best_streak = result.best_streak.max(result.current_streak);
+                // A win that took a few hits isn't much of a record.
+                if record.stats.lives_used == 0 {
+                    let time = record.stats.time;
+                    result.best_time = Some(result.best_time.map_or(time, |best| best.min(time)));
+                }
+            } else {
+                result.current_streak = 0;
+            }
+        }
+        result
+    }
+}
+
+pub fn load() -> Result<Records, FileError> {
+    match file_format::read_data_file(RECORDS_FILE_NAME)? {
+        Some(text) => Records::from_text(&text),
+        None => Ok(Records::default()),
+    }
+}
+
+/// Adds a game to the end of the records file.
+pub fn append(record: &GameRecord) -> Result<(), FileError> {
+    file_format::append_data_file(
+        RECORDS_FILE_NAME,
+        &file_format::header_line(RECORDS_KIND, RECORDS_VERSION),
+        &record.line(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beginner() -> BoardConfig {
+        BoardConfig::custom(9, 9, 10).unwrap()
+    }
+
+    const TEXT: &str = "minesweeper-rs records 1
+game 1 9 9 10 won 12000 20 20 25 3 2
+game 2 9 9 10 won 8000 20 20 22 4 1 1
+game 3 9 9 10 won 10000 20 20 23 5 0
+game 4 9 9 garbage
+game 5 16 16 40 won 5000 40 40 50 10 5
+game 6 9 9 10 lost 3000 4 18 5 1 0
+game 7 9 9 10 won 11000 19 19 20 2 2
+";
+
+    #[test]
+    fn skips_a_damaged_line() {
+        let records = Records::from_text(TEXT).unwrap();
+
+        assert_eq!(records.skipped_lines(), 1);
+        let finished_at: Vec<u64> = records
+            .history()
+            .iter()
+            .map(|record| record.finished_at)
+            .collect();
+        assert_eq!(finished_at, vec![1, 2, 3, 5, 6, 7]);
+        assert_eq!(records.history()[1].stats.lives_used, 1);
+        assert_eq!(
+            records.for_config(&beginner(), &RecordRules::default()),
+            ConfigRecords {
+                played: 5,
+                won: 4,
+                current_streak: 1,
+                best_streak: 3,
+                // The 8 second win used a life.
+                best_time: Some(Duration::from_millis(10000)),
+            }
+        );
+    }
+
+    #[test]
+    fn skips_a_truncated_last_line() {
+        let text = format!("{}game 8 9 9 10 won 900", TEXT);
+        let records = Records::from_text(&text).unwrap();
+
+        assert_eq!(records.skipped_lines(), 2);
+        assert_eq!(records.history().len(), 6);
+        assert_eq!(
+            records
+                .for_config(&beginner(), &RecordRules::default())
+                .best_time,
+            Some(Duration::from_millis(10000))
+        );
+        assert_eq!(
+            records
+                .for_config(&beginner(), &RecordRules::default())
+                .current_streak,
+            1
+        );
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let record = Records::from_text(TEXT).unwrap().history()[1];
+        assert_eq!(GameRecord::parse_line(&record.line()), Some(record));
+
+        let record = GameRecord {
+            rules: RecordRules {
+                first_click_policy: FirstClickPolicy::SafeOpening,
+                no_guess: true,
+                lives: 3,
+            },
+            ..record
+        };
+        assert_eq!(GameRecord::parse_line(&record.line()), Some(record));
+    }
+
+    #[test]
+    fn games_are_only_compared_under_the_same_rules() {
+        let text = format!(
+            "{}{}\n{}\n{}\n",
+            TEXT,
+            "game 8 9 9 10 won 2000 20 20 20 0 0 first-click opening no-guess",
+            "game 9 9 9 10 lost 1000 1 20 1 0 0 first-click opening",
+            "game 10 9 9 10 won 4000 20 20 20 0 0 1 lives 3"
+        );
+        let records = Records::from_text(&text).unwrap();
+        assert_eq!(records.skipped_lines(), 1);
+
+        // The standard games are the same as without the others.
+        assert_eq!(
+            records.for_config(&beginner(), &RecordRules::default()),
+            Records::from_text(TEXT)
+                .unwrap()
+                .for_config(&beginner(), &RecordRules::default())
+        );
+
+        let no_guess_opening = RecordRules {
+            first_click_policy: FirstClickPolicy::SafeOpening,
+            no_guess: true,
+            lives: 1,
+        };
+        let config_records = records.for_config(&beginner(), &no_guess_opening);
+        assert_eq!(config_records.played, 1);
+        assert_eq!(config_records.best_time, Some(Duration::from_millis(2000)));
+
+        let three_lives = RecordRules {
+            lives: 3,
+            ..RecordRules::default()
+        };
+        let config_records = records.for_config(&beginner(), &three_lives);
+        assert_eq!((config_records.played, config_records.won), (1, 1));
+        assert_eq!(config_records.best_time, None);
+    }
+
+    #[test]
+    fn skips_lines_with_unknown_rules() {
+        let text = "game 1 9 9 10 won 12000 20 20 25 3 2 first-click sometimes\n\
+                    game 2 9 9 10 won 12000 20 20 25 3 2 lives 0\n\
+                    game 3 9 9 10 won 12000 20 20 25 3 2 hard\n";
+        let records = Records::from_text(text).unwrap();
+        assert_eq!(records.skipped_lines(), 3);
+    }
+
+    #[test]
+    fn refuses_a_newer_version() {
+        assert!(matches!(
+            Records::from_text("minesweeper-rs records 2\n"),
+            Err(FileError::UnsupportedVersion(version)) if version == "2"
+        ));
+    }
+}
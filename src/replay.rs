@@ -0,0 +1,348 @@
+use crate::board::{IndexHelper, TileCoordinate};
+use crate::file_format::{self, BoardSettings, FileError, Lines};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const REPLAY_VERSION: u32 = 4;
+const REPLAY_KIND: &str = "replay";
+const REPLAY_DIR_NAME: &str = "replays";
+
+/// Something the player did, in the order it reached the game.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ReplayAction {
+    Resized { width: f32, height: f32 },
+    PointerMoved { x: f32, y: f32 },
+    Reveal(TileCoordinate),
+    CycleMineState(TileCoordinate),
+    Chord(TileCoordinate),
+    Undo,
+    Redo,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ReplayEvent {
+    /// How long after the start of the game this happened.
+    pub time: Duration,
+    pub action: ReplayAction,
+}
+
+/// A recording of one game from the first event to the last.
+///
+/// The format is plain text:
+///
+/// ```text
+/// minesweeper-rs replay 4
+/// board 9 9 10
+/// seed 1234
+/// first-click safe
+/// generation random
+/// topology hex
+/// mines-per-tile 1
+/// lives 1
+/// mines
+/// ..*......
+/// (one row of `*` and `.` per line, with `2` to `9` for tiles holding more
+/// mines, or "mines deferred" and no rows)
+/// events
+/// 0 resize 800 600
+/// 350 move 412.5 230
+/// 420 reveal 4 3
+/// (one "<milliseconds> <action>" per line until the end of the file)
+/// ```
+///
+/// The mines are kept alongside the seed so that a replay still plays back
+/// the same board if the way mines are generated ever changes.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Replay {
+    pub settings: BoardSettings,
+    pub mines: Option<Vec<i32>>,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    pub fn to_text(&self) -> String {
+        let config = self.settings.config;
+
+        let mut lines = vec![file_format::header_line(REPLAY_KIND, REPLAY_VERSION)];
+        lines.extend(file_format::settings_lines(&self.settings));
+        match &self.mines {
+            Some(mines) => {
+                lines.push("mines".to_owned());
+                lines.extend(file_format::grid_lines(
+                    &config,
+                    mines,
+                    file_format::mine_to_char,
+                ));
+            }
+            None => lines.push("mines deferred".to_owned()),
+        }
+
+        lines.push("events".to_owned());
+        for event in &self.events {
+            let time = event.time.as_millis();
+            lines.push(match event.action {
+                ReplayAction::Resized { width, height } => {
+                    format!("{} resize {} {}", time, width, height)
+                }
+                ReplayAction::PointerMoved { x, y } => format!("{} move {} {}", time, x, y),
+                ReplayAction::Reveal(tile) => format!("{} reveal {} {}", time, tile.x, tile.y),
+                ReplayAction::CycleMineState(tile) => {
+                    format!("{} cycle {} {}", time, tile.x, tile.y)
+                }
+                ReplayAction::Chord(tile) => format!("{} chord {} {}", time, tile.x, tile.y),
+                ReplayAction::Undo => format!("{} undo", time),
+                ReplayAction::Redo => format!("{} redo", time),
+            });
+        }
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, FileError> {
+        let mut lines = Lines::new(text, REPLAY_KIND, REPLAY_VERSION)?;
+
+        let settings = lines.settings()?;
+        let mines = match lines.field("mines")?.as_slice() {
+            [] => Some(lines.grid(&settings.config, 0, file_format::mine_from_char)?),
+            ["deferred"] => None,
+            _ => return Err(lines.malformed()),
+        };
+
+        if !lines.field("events")?.is_empty() {
+            return Err(lines.malformed());
+        }
+        let config = settings.config;
+        let index_helper = IndexHelper::new(config.width(), config.height());
+        let mut events = Vec::new();
+        while let Some(line) = lines.try_next() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (time, action) = match words.as_slice() {
+                [time, action @ ..] => (Duration::from_millis(lines.parse(time)?), action),
+                _ => return Err(lines.malformed()),
+            };
+            let tile = |x: &str, y: &str| -> Result<TileCoordinate, FileError> {
+                let tile = TileCoordinate {
+                    x: lines.parse(x)?,
+                    y: lines.parse(y)?,
+                };
+                if !index_helper.is_in_bounds(tile.x, tile.y) {
+                    return Err(lines.malformed());
+                }
+                Ok(tile)
+            };
+            let action = match action {
+                ["resize", width, height] => ReplayAction::Resized {
+                    width: lines.parse(width)?,
+                    height: lines.parse(height)?,
+                },
+                ["move", x, y] => ReplayAction::PointerMoved {
+                    x: lines.parse(x)?,
+                    y: lines.parse(y)?,
+                },
+                ["reveal", x, y] => ReplayAction::Reveal(tile(x, y)?),
+                ["cycle", x, y] => ReplayAction::CycleMineState(tile(x, y)?),
+                ["chord", x, y] => ReplayAction::Chord(tile(x, y)?),
+                ["undo"] => ReplayAction::Undo,
+                ["redo"] => ReplayAction::Redo,
+                _ => return Err(lines.malformed()),
+            };
+            events.push(ReplayEvent { time, action });
+        }
+
+        Ok(Self {
+            settings,
+            mines,
+            events,
+        })
+    }
+}
+
+/// Collects the events of the game being played.
+pub struct ReplayRecorder {
+    started: Instant,
+    events: Vec<ReplayEvent>,
+}
+
+impl Default for ReplayRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReplayRecorder {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, action: ReplayAction) {
+        self.events.push(ReplayEvent {
+            time: self.started.elapsed(),
+            action,
+        });
+    }
+
+    pub fn events(&self) -> &[ReplayEvent] {
+        &self.events
+    }
+}
+
+/// Hands out the events of a replay as their time comes.
+pub struct ReplayPlayer {
+    events: Vec<ReplayEvent>,
+    next_event: usize,
+    started: Instant,
+    speed: f64,
+}
+
+impl ReplayPlayer {
+    /// A speed of 2.0 plays the replay twice as fast as it was recorded.
+    pub fn new(events: Vec<ReplayEvent>, speed: f64) -> Self {
+        Self {
+            events,
+            next_event: 0,
+            started: Instant::now(),
+            speed,
+        }
+    }
+
+    /// Returns the events that are due since the last call.
+    pub fn due_events(&mut self) -> Vec<ReplayAction> {
+        let position = self.started.elapsed().mul_f64(self.speed);
+        let mut result = Vec::new();
+        while let Some(event) = self.events.get(self.next_event) {
+            if event.time > position {
+                break;
+            }
+            result.push(event.action);
+            self.next_event += 1;
+        }
+        result
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_event == self.events.len()
+    }
+}
+
+pub fn load(path: &Path) -> Result<Replay, FileError> {
+    Replay::from_text(&fs::read_to_string(path)?)
+}
+
+/// Keeps a replay in the replays folder of the data directory, named after
+/// when it was stored.
+pub fn store(replay: &Replay) -> Result<(), FileError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let name = format!(
+        "{}/{}-seed{}.txt",
+        REPLAY_DIR_NAME,
+        now.as_millis(),
+        replay.settings.seed
+    );
+    file_format::write_data_file(&name, replay.to_text())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{BoardConfig, FirstClickPolicy, GenerationMode};
+    use crate::topology::Topology;
+
+    fn replay() -> Replay {
+        let config = BoardConfig::custom(3, 2, 1).unwrap();
+        let tile = TileCoordinate { x: 2, y: 1 };
+        let events = [
+            (
+                0,
+                ReplayAction::Resized {
+                    width: 800.0,
+                    height: 600.0,
+                },
+            ),
+            (350, ReplayAction::PointerMoved { x: 412.5, y: 230.0 }),
+            (420, ReplayAction::Reveal(tile)),
+            (500, ReplayAction::CycleMineState(tile)),
+            (600, ReplayAction::Chord(tile)),
+            (700, ReplayAction::Undo),
+            (800, ReplayAction::Redo),
+        ];
+        Replay {
+            settings: BoardSettings {
+                config,
+                seed: 1234,
+                first_click_policy: FirstClickPolicy::SafeTile,
+                generation_mode: GenerationMode::Random,
+                topology: Topology::default(),
+                max_mines_per_tile: 1,
+                lives: 1,
+            },
+            mines: Some(vec![0, 0, 0, 0, 1, 0]),
+            events: events
+                .into_iter()
+                .map(|(time, action)| ReplayEvent {
+                    time: Duration::from_millis(time),
+                    action,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let replay = replay();
+        assert_eq!(Replay::from_text(&replay.to_text()).unwrap(), replay);
+
+        let deferred = Replay {
+            mines: None,
+            ..replay
+        };
+        assert_eq!(Replay::from_text(&deferred.to_text()).unwrap(), deferred);
+    }
+
+    #[test]
+    fn rejects_tiles_outside_of_the_board() {
+        for event in ["420 reveal 100 -5", "420 cycle 3 0", "420 chord 0 2"] {
+            let text = format!("{}{}\n", replay().to_text(), event);
+            assert!(matches!(
+                Replay::from_text(&text),
+                Err(FileError::Malformed { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_mines_that_dont_fit_around_the_opening() {
+        let mut replay = replay();
+        replay.settings.config = BoardConfig::custom(3, 2, 5).unwrap();
+        replay.settings.first_click_policy = FirstClickPolicy::SafeOpening;
+        replay.mines = None;
+        assert!(matches!(
+            Replay::from_text(&replay.to_text()),
+            Err(FileError::Malformed { .. })
+        ));
+    }
+
+    #[test]
+    fn player_hands_out_events_as_they_come_due() {
+        let events = replay().events;
+        let mut player = ReplayPlayer::new(events.clone(), 1.0);
+        assert_eq!(player.due_events(), vec![events[0].action]);
+        assert!(player.due_events().is_empty());
+        assert!(!player.is_finished());
+
+        // At a thousand times the speed, the whole replay is over in less
+        // than a millisecond.
+        let mut player = ReplayPlayer::new(events.clone(), 1000.0);
+        std::thread::sleep(Duration::from_millis(5));
+        let actions: Vec<ReplayAction> = events.iter().map(|event| event.action).collect();
+        assert_eq!(player.due_events(), actions);
+        assert!(player.is_finished());
+    }
+}
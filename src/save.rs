@@ -0,0 +1,280 @@
+use crate::board::{BoardSnapshot, MineState};
+use crate::file_format::{self, BoardSettings, FileError, Lines};
+use crate::stats::ClickCounts;
+use std::time::Duration;
+
+// Bump this whenever the layout below changes. Files with any other version
+// are refused rather than guessed at.
+const SAVE_VERSION: u32 = 5;
+const SAVE_KIND: &str = "save";
+const SAVE_FILE_NAME: &str = "save.txt";
+
+/// A game in progress, as written to disk when the window closes.
+///
+/// The format is plain text, one field per line:
+///
+/// ```text
+/// minesweeper-rs save 5
+/// board 9 9 10
+/// seed 1234
+/// first-click safe
+/// generation no-guess 250
+/// topology square
+/// mines-per-tile 1
+/// lives 1
+/// elapsed-ms 5321
+/// clicks 12 3 4
+/// (left, right and chord clicks so far)
+/// mines
+/// ..*......
+/// (one row of `*` and `.` per line, with `2` to `9` for tiles holding more
+/// mines, or "mines deferred" and no rows)
+/// tiles
+/// RRF......
+/// (one row per line: `.` empty, `F` flag, `2` to `9` for a flag on more
+/// mines, `?` question, `R` revealed)
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SavedGame {
+    pub board: BoardSnapshot,
+    pub elapsed: Duration,
+    pub clicks: ClickCounts,
+}
+
+impl SavedGame {
+    pub fn to_text(&self) -> String {
+        let board = &self.board;
+        let config = board.config;
+
+        let mut lines = vec![file_format::header_line(SAVE_KIND, SAVE_VERSION)];
+        lines.extend(file_format::settings_lines(&BoardSettings {
+            config,
+            seed: board.seed,
+            first_click_policy: board.first_click_policy,
+            generation_mode: board.generation_mode,
+            topology: board.topology,
+            max_mines_per_tile: board.max_mines_per_tile,
+            lives: board.lives,
+        }));
+        lines.push(format!("elapsed-ms {}", self.elapsed.as_millis()));
+        lines.push(format!(
+            "clicks {} {} {}",
+            self.clicks.left, self.clicks.right, self.clicks.chord
+        ));
+
+        match &board.mines {
+            Some(mines) => {
+                lines.push("mines".to_owned());
+                lines.extend(file_format::grid_lines(
+                    &config,
+                    mines,
+                    file_format::mine_to_char,
+                ));
+            }
+            None => lines.push("mines deferred".to_owned()),
+        }
+        lines.push("tiles".to_owned());
+        lines.extend(file_format::grid_lines(
+            &config,
+            &board.mine_states,
+            mine_state_to_char,
+        ));
+
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, FileError> {
+        let mut lines = Lines::new(text, SAVE_KIND, SAVE_VERSION)?;
+
+        let settings = lines.settings()?;
+        let config = settings.config;
+        let elapsed = match lines.field("elapsed-ms")?.as_slice() {
+            [elapsed] => Duration::from_millis(lines.parse(elapsed)?),
+            _ => return Err(lines.malformed()),
+        };
+        let clicks = match lines.field("clicks")?.as_slice() {
+            [left, right, chord] => ClickCounts {
+                left: lines.parse(left)?,
+                right: lines.parse(right)?,
+                chord: lines.parse(chord)?,
+            },
+            _ => return Err(lines.malformed()),
+        };
+
+        let mines = match lines.field("mines")?.as_slice() {
+            [] => Some(lines.grid(&config, 0, file_format::mine_from_char)?),
+            ["deferred"] => None,
+            _ => return Err(lines.malformed()),
+        };
+        if !lines.field("tiles")?.is_empty() {
+            return Err(lines.malformed());
+        }
+        let mine_states = lines.grid(&config, MineState::Empty, mine_state_from_char)?;
+
+        Ok(Self {
+            board: BoardSnapshot {
+                config,
+                seed: settings.seed,
+                first_click_policy: settings.first_click_policy,
+                generation_mode: settings.generation_mode,
+                topology: settings.topology,
+                max_mines_per_tile: settings.max_mines_per_tile,
+                lives: settings.lives,
+                mines,
+                mine_states,
+            },
+            elapsed,
+            clicks,
+        })
+    }
+}
+
+fn mine_state_to_char(state: MineState) -> char {
+    match state {
+        MineState::Empty => '.',
+        MineState::Flag(1) => 'F',
+        MineState::Flag(mines) => char::from_digit(mines as u32, 10).unwrap_or('F'),
+        MineState::Question => '?',
+        MineState::Revealed => 'R',
+    }
+}
+
+fn mine_state_from_char(c: char) -> Option<MineState> {
+    match c {
+        '.' => Some(MineState::Empty),
+        'F' => Some(MineState::Flag(1)),
+        '2'..='9' => c.to_digit(10).map(|mines| MineState::Flag(mines as i32)),
+        '?' => Some(MineState::Question),
+        'R' => Some(MineState::Revealed),
+        _ => None,
+    }
+}
+
+/// Reads the saved game, if there is one.
+pub fn load() -> Result<Option<SavedGame>, FileError> {
+    match file_format::read_data_file(SAVE_FILE_NAME)? {
+        Some(text) => SavedGame::from_text(&text).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Replaces the saved game.
+pub fn store(game: &SavedGame) -> Result<(), FileError> {
+    file_format::write_data_file(SAVE_FILE_NAME, game.to_text())
+}
+
+/// Removes the saved game, if there is one.
+pub fn discard() -> Result<(), FileError> {
+    file_format::remove_data_file(SAVE_FILE_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{Board, BoardConfig, FirstClickPolicy, GenerationMode, SnapshotError};
+    use crate::topology::Topology;
+
+    fn saved_game() -> SavedGame {
+        SavedGame {
+            board: BoardSnapshot {
+                config: BoardConfig::custom(4, 3, 3).unwrap(),
+                seed: 1234,
+                first_click_policy: FirstClickPolicy::SafeOpening,
+                generation_mode: GenerationMode::NoGuess { max_attempts: 250 },
+                topology: Topology::default(),
+                max_mines_per_tile: 2,
+                lives: 2,
+                mines: Some(vec![0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1]),
+                // "R2..", "R?.." and "R..F", a column at a time.
+                mine_states: vec![
+                    MineState::Revealed,
+                    MineState::Revealed,
+                    MineState::Revealed,
+                    MineState::Flag(2),
+                    MineState::Question,
+                    MineState::Empty,
+                    MineState::Empty,
+                    MineState::Empty,
+                    MineState::Empty,
+                    MineState::Empty,
+                    MineState::Empty,
+                    MineState::Flag(1),
+                ],
+            },
+            elapsed: Duration::from_millis(5321),
+            clicks: ClickCounts {
+                left: 12,
+                right: 3,
+                chord: 4,
+            },
+        }
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let saved_game = saved_game();
+        assert_eq!(
+            SavedGame::from_text(&saved_game.to_text()).unwrap(),
+            saved_game
+        );
+
+        let mut deferred = saved_game;
+        deferred.board.mines = None;
+        deferred.board.mine_states = vec![MineState::Empty; 12];
+        assert_eq!(SavedGame::from_text(&deferred.to_text()).unwrap(), deferred);
+    }
+
+    #[test]
+    fn rejects_a_damaged_save() {
+        let text = saved_game().to_text();
+        let lines: Vec<&str> = text.lines().collect();
+        let damaged = [
+            // The last row of tiles is missing.
+            lines[..lines.len() - 1].join("\n"),
+            // A tile that isn't any of the states.
+            text.replace("R2..", "RX.."),
+            text.replace("mines-per-tile 2", "mines-per-tile 0"),
+            text.replace("lives 2", "lives 0"),
+            text.replace("clicks 12 3 4", "clicks 12 3"),
+            text.replace("elapsed-ms 5321\n", ""),
+        ];
+        for text in damaged {
+            assert!(
+                matches!(
+                    SavedGame::from_text(&text),
+                    Err(FileError::Malformed { .. })
+                ),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn leaves_checking_the_board_to_the_board() {
+        // A flag on more mines than a tile can hold reads fine, but the board
+        // won't be built from it.
+        let text = saved_game().to_text().replace("R2..", "R3..");
+        let saved_game = SavedGame::from_text(&text).unwrap();
+        assert_eq!(
+            Board::from_snapshot(saved_game.board).err(),
+            Some(SnapshotError::InvalidFlag {
+                max_mines_per_tile: 2,
+                found: 3
+            })
+        );
+    }
+
+    #[test]
+    fn refuses_a_newer_version() {
+        let text = saved_game()
+            .to_text()
+            .replace("minesweeper-rs save 5", "minesweeper-rs save 6");
+        assert!(matches!(
+            SavedGame::from_text(&text),
+            Err(FileError::UnsupportedVersion(version)) if version == "6"
+        ));
+    }
+}
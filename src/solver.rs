@@ -0,0 +1,1191 @@
+use crate::board::{IndexHelper, TileCoordinate};
+use crate::topology::Topology;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+/// What a player can see of a single tile.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum VisibleTile {
+    Unrevealed,
+    /// Flags are trusted to be mines, the same way chording trusts them.
+    Flagged,
+    Revealed(i32),
+}
+
+/// The board as the player sees it: revealed counts, flags and everything
+/// that is still hidden.
+pub struct VisibleBoard {
+    width: i32,
+    height: i32,
+    num_mines: i32,
+    max_mines_per_tile: i32,
+    index_helper: IndexHelper,
+    topology: Topology,
+    tiles: Vec<VisibleTile>,
+}
+
+impl VisibleBoard {
+    pub fn new(width: i32, height: i32, num_mines: i32) -> Self {
+        Self {
+            width,
+            height,
+            num_mines,
+            max_mines_per_tile: 1,
+            index_helper: IndexHelper::new(width, height),
+            topology: Topology::default(),
+            tiles: vec![VisibleTile::Unrevealed; (width * height) as usize],
+        }
+    }
+
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// The solver only knows about tiles with a single mine, so it doesn't
+    /// make any claims about boards where tiles can hold more.
+    pub fn with_max_mines_per_tile(mut self, max_mines_per_tile: i32) -> Self {
+        self.max_mines_per_tile = max_mines_per_tile;
+        self
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn num_mines(&self) -> i32 {
+        self.num_mines
+    }
+
+    pub fn tile(&self, tile_coordinate: &TileCoordinate) -> VisibleTile {
+        self.tiles[self
+            .index_helper
+            .compute_index(tile_coordinate.x, tile_coordinate.y)]
+    }
+
+    pub fn set_tile(&mut self, tile_coordinate: &TileCoordinate, tile: VisibleTile) {
+        let index = self
+            .index_helper
+            .compute_index(tile_coordinate.x, tile_coordinate.y);
+        self.tiles[index] = tile;
+    }
+
+    pub(crate) fn tile_at(&self, index: usize) -> VisibleTile {
+        self.tiles[index]
+    }
+
+    pub(crate) fn coordinate(&self, index: usize) -> TileCoordinate {
+        TileCoordinate {
+            x: self.index_helper.compute_x_from_index(index),
+            y: self.index_helper.compute_y_from_index(index),
+        }
+    }
+
+    pub(crate) fn neighbors(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.topology.neighbors(&self.index_helper, index)
+    }
+}
+
+/// Which tier of the solver proved a deduction.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Rule {
+    /// A single number already has all of its mines, or needs every tile around it.
+    SingleTile,
+    /// Two overlapping numbers constrain the tiles only one of them touches.
+    Subset,
+    /// Every arrangement of mines consistent with the board agrees on the tile.
+    Enumeration,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Deduction {
+    pub tile: TileCoordinate,
+    pub is_mine: bool,
+    pub rule: Rule,
+    /// The revealed tiles whose numbers prove the deduction.
+    pub reasons: Vec<TileCoordinate>,
+}
+
+/// Returns the tiles that the cheapest tier able to make progress can prove to
+/// be safe or mined. An empty result means the player has to guess, or that
+/// tiles can hold more than one mine.
+pub fn next_deductions(board: &VisibleBoard) -> Vec<Deduction> {
+    if board.max_mines_per_tile > 1 {
+        return Vec::new();
+    }
+    let constraints = match build_constraints(board) {
+        Some(constraints) => constraints,
+        None => return Vec::new(),
+    };
+
+    let deductions = single_tile_deductions(board, &constraints);
+    if !deductions.is_empty() {
+        return deductions;
+    }
+
+    let deductions = subset_deductions(board, &constraints);
+    if !deductions.is_empty() {
+        return deductions;
+    }
+
+    enumeration_deductions(board, &constraints)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TileProbability {
+    pub tile: TileCoordinate,
+    pub probability: f64,
+}
+
+/// Returns the exact chance that each tile the player can't see holds a mine,
+/// given the numbers on the board and how many mines are left unaccounted for.
+/// Flagged tiles are reported as certain mines. Returns `None` if the board
+/// contradicts itself, or if tiles can hold more than one mine.
+pub fn mine_probabilities(board: &VisibleBoard) -> Option<Vec<TileProbability>> {
+    if board.max_mines_per_tile > 1 {
+        return None;
+    }
+    let constraints = build_constraints(board)?;
+    let enumeration = enumerate_frontier::<f64>(board, &constraints);
+    if enumeration.total <= 0.0 {
+        return None;
+    }
+
+    let mut probabilities: Vec<(usize, f64)> = enumeration
+        .cells
+        .iter()
+        .map(|cell| {
+            let weight = cell.mine + cell.safe;
+            let probability = if weight > 0.0 {
+                cell.mine / weight
+            } else {
+                0.0
+            };
+            (cell.index, probability)
+        })
+        .chain(
+            (0..board.tiles.len())
+                .filter(|index| board.tiles[*index] == VisibleTile::Flagged)
+                .map(|index| (index, 1.0)),
+        )
+        .collect();
+    probabilities.sort_by_key(|(index, _)| *index);
+
+    Some(
+        probabilities
+            .into_iter()
+            .map(|(index, probability)| TileProbability {
+                tile: board.coordinate(index),
+                probability,
+            })
+            .collect(),
+    )
+}
+
+/// Plays a board with a known layout from `start`, only ever revealing tiles the
+/// solver can prove are safe. Returns whether that clears the whole board.
+pub(crate) fn can_clear_without_guessing(
+    mut board: VisibleBoard,
+    mines: &[i32],
+    neighbor_counts: &[i32],
+    start: &TileCoordinate,
+) -> bool {
+    let start = board.index_helper.compute_index(start.x, start.y);
+    if mines[start] > 0 {
+        return false;
+    }
+    reveal_from(&mut board, neighbor_counts, start);
+
+    let is_safe_and_hidden = |board: &VisibleBoard, index: usize| {
+        mines[index] == 0 && board.tiles[index] == VisibleTile::Unrevealed
+    };
+    while (0..board.tiles.len()).any(|index| is_safe_and_hidden(&board, index)) {
+        let deductions = next_deductions(&board);
+        if deductions.is_empty() {
+            return false;
+        }
+
+        for deduction in deductions {
+            let index = board
+                .index_helper
+                .compute_index(deduction.tile.x, deduction.tile.y);
+            if deduction.is_mine {
+                board.tiles[index] = VisibleTile::Flagged;
+            } else {
+                reveal_from(&mut board, neighbor_counts, index);
+            }
+        }
+    }
+    true
+}
+
+/// Reveals a tile, and everything around it if it has no neighboring mines.
+fn reveal_from(board: &mut VisibleBoard, neighbor_counts: &[i32], start: usize) {
+    let mut sweeps = VecDeque::new();
+    sweeps.push_back(start);
+    while let Some(index) = sweeps.pop_front() {
+        if board.tiles[index] != VisibleTile::Unrevealed {
+            continue;
+        }
+        board.tiles[index] = VisibleTile::Revealed(neighbor_counts[index]);
+        if neighbor_counts[index] == 0 {
+            let neighbors: Vec<usize> = board.neighbors(index).collect();
+            sweeps.extend(neighbors);
+        }
+    }
+}
+
+/// A revealed number: exactly `mines` of the unknown `cells` around `tile` are mines.
+pub(crate) struct Constraint {
+    pub(crate) tile: usize,
+    pub(crate) cells: Vec<usize>,
+    pub(crate) mines: i32,
+}
+
+/// Returns `None` if the board contradicts itself, e.g. a number has more flags
+/// around it than its count.
+pub(crate) fn build_constraints(board: &VisibleBoard) -> Option<Vec<Constraint>> {
+    let mut constraints = Vec::new();
+    for (index, tile) in board.tiles.iter().enumerate() {
+        if let VisibleTile::Revealed(count) = *tile {
+            let mut cells = Vec::new();
+            let mut mines = count;
+            for neighbor in board.neighbors(index) {
+                match board.tile_at(neighbor) {
+                    VisibleTile::Unrevealed => cells.push(neighbor),
+                    VisibleTile::Flagged => mines -= 1,
+                    VisibleTile::Revealed(_) => {}
+                }
+            }
+
+            if mines < 0 || mines as usize > cells.len() {
+                return None;
+            }
+            if !cells.is_empty() {
+                cells.sort_unstable();
+                constraints.push(Constraint {
+                    tile: index,
+                    cells,
+                    mines,
+                });
+            }
+        }
+    }
+    Some(constraints)
+}
+
+struct DeductionSet<'a> {
+    board: &'a VisibleBoard,
+    seen: HashSet<usize>,
+    deductions: Vec<Deduction>,
+}
+
+impl<'a> DeductionSet<'a> {
+    fn new(board: &'a VisibleBoard) -> Self {
+        Self {
+            board,
+            seen: HashSet::new(),
+            deductions: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, index: usize, is_mine: bool, rule: Rule, reasons: &[usize]) {
+        if self.seen.insert(index) {
+            self.deductions.push(Deduction {
+                tile: self.board.coordinate(index),
+                is_mine,
+                rule,
+                reasons: reasons
+                    .iter()
+                    .map(|reason| self.board.coordinate(*reason))
+                    .collect(),
+            });
+        }
+    }
+}
+
+fn single_tile_deductions(board: &VisibleBoard, constraints: &[Constraint]) -> Vec<Deduction> {
+    let mut deductions = DeductionSet::new(board);
+    for constraint in constraints {
+        let is_mine = if constraint.mines == 0 {
+            false
+        } else if constraint.mines as usize == constraint.cells.len() {
+            true
+        } else {
+            continue;
+        };
+
+        for cell in &constraint.cells {
+            deductions.push(*cell, is_mine, Rule::SingleTile, &[constraint.tile]);
+        }
+    }
+    deductions.deductions
+}
+
+fn subset_deductions(board: &VisibleBoard, constraints: &[Constraint]) -> Vec<Deduction> {
+    let mut constraints_by_cell: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, constraint) in constraints.iter().enumerate() {
+        for cell in &constraint.cells {
+            constraints_by_cell.entry(*cell).or_default().push(i);
+        }
+    }
+
+    let mut deductions = DeductionSet::new(board);
+    for (i, first) in constraints.iter().enumerate() {
+        let overlapping: BTreeSet<usize> = first
+            .cells
+            .iter()
+            .flat_map(|cell| constraints_by_cell[cell].iter().copied())
+            .filter(|j| *j != i)
+            .collect();
+
+        for j in overlapping {
+            let second = &constraints[j];
+            let only_first: Vec<usize> = first
+                .cells
+                .iter()
+                .filter(|cell| second.cells.binary_search(cell).is_err())
+                .copied()
+                .collect();
+            let only_second: Vec<usize> = second
+                .cells
+                .iter()
+                .filter(|cell| first.cells.binary_search(cell).is_err())
+                .copied()
+                .collect();
+
+            // The difference in mines can only be made up entirely by the tiles
+            // that the second number touches alone if every one of them is a mine
+            // and none of the tiles the first number touches alone are.
+            if second.mines - first.mines == only_second.len() as i32 {
+                let reasons = [first.tile, second.tile];
+                for cell in only_second {
+                    deductions.push(cell, true, Rule::Subset, &reasons);
+                }
+                for cell in only_first {
+                    deductions.push(cell, false, Rule::Subset, &reasons);
+                }
+            }
+        }
+    }
+    deductions.deductions
+}
+
+fn enumeration_deductions(board: &VisibleBoard, constraints: &[Constraint]) -> Vec<Deduction> {
+    let enumeration = enumerate_frontier::<bool>(board, constraints);
+    let mut deductions = DeductionSet::new(board);
+    if !enumeration.total {
+        return deductions.deductions;
+    }
+
+    for cell in &enumeration.cells {
+        if !cell.mine {
+            deductions.push(cell.index, false, Rule::Enumeration, &cell.reasons);
+        } else if !cell.safe {
+            deductions.push(cell.index, true, Rule::Enumeration, &cell.reasons);
+        }
+    }
+    deductions.deductions
+}
+
+/// A way of adding up the arrangements of mines on a board. `bool` answers
+/// whether an arrangement exists, `f64` counts (a scaled number of) them.
+pub(crate) trait Weight: Copy + PartialEq {
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn add(self, other: Self) -> Self;
+    fn mul(self, other: Self) -> Self;
+    fn count(n: usize) -> Self;
+    /// The number of ways to choose `k` out of `n` tiles for every `k` up to
+    /// `max_k`. The values may share a common scale factor.
+    fn binomials(n: usize, max_k: usize) -> Vec<Self>;
+    /// A factor that brings the values into a reasonable range.
+    fn normalizer(values: &[Self]) -> Self;
+}
+
+impl Weight for bool {
+    const ZERO: Self = false;
+    const ONE: Self = true;
+
+    fn add(self, other: Self) -> Self {
+        self || other
+    }
+
+    fn mul(self, other: Self) -> Self {
+        self && other
+    }
+
+    fn count(n: usize) -> Self {
+        n > 0
+    }
+
+    fn binomials(n: usize, max_k: usize) -> Vec<Self> {
+        (0..=max_k).map(|k| k <= n).collect()
+    }
+
+    fn normalizer(_values: &[Self]) -> Self {
+        true
+    }
+}
+
+impl Weight for f64 {
+    const ZERO: Self = 0.0;
+    const ONE: Self = 1.0;
+
+    fn add(self, other: Self) -> Self {
+        self + other
+    }
+
+    fn mul(self, other: Self) -> Self {
+        self * other
+    }
+
+    fn count(n: usize) -> Self {
+        n as f64
+    }
+
+    fn binomials(n: usize, max_k: usize) -> Vec<Self> {
+        // Work with logarithms so that large boards don't overflow.
+        let mut logs = Vec::with_capacity(max_k + 1);
+        let mut current = 0.0;
+        for k in 0..=max_k.min(n) {
+            if k > 0 {
+                current += ((n - k + 1) as f64).ln() - (k as f64).ln();
+            }
+            logs.push(current);
+        }
+        let max = logs.iter().copied().fold(f64::MIN, f64::max);
+        let mut result: Vec<f64> = logs.iter().map(|log| (log - max).exp()).collect();
+        result.resize(max_k + 1, 0.0);
+        result
+    }
+
+    fn normalizer(values: &[Self]) -> Self {
+        let max = values.iter().copied().fold(0.0, f64::max);
+        if max > 0.0 {
+            1.0 / max
+        } else {
+            1.0
+        }
+    }
+}
+
+pub(crate) struct CellWeight<W> {
+    pub(crate) index: usize,
+    /// The weight of the arrangements in which the tile is a mine.
+    pub(crate) mine: W,
+    /// The weight of the arrangements in which the tile is safe.
+    pub(crate) safe: W,
+    pub(crate) reasons: Vec<usize>,
+}
+
+pub(crate) struct FrontierEnumeration<W> {
+    /// Every unrevealed, unflagged tile on the board.
+    pub(crate) cells: Vec<CellWeight<W>>,
+    /// The weight of all arrangements consistent with the board.
+    pub(crate) total: W,
+}
+
+/// Tiles that are touched by exactly the same numbers. They are interchangeable,
+/// so only how many of them are mines matters.
+struct Group {
+    cells: Vec<usize>,
+    /// For each number touching the group, its index within the component and how
+    /// many of its tiles come after this group.
+    memberships: Vec<(usize, usize)>,
+}
+
+struct ComponentEnumeration<W> {
+    /// The weight of the arrangements with a given number of mines.
+    totals: Vec<W>,
+    /// For each group, the mine and safe weights by number of mines.
+    groups: Vec<(Group, Vec<W>, Vec<W>)>,
+    reasons: Vec<usize>,
+}
+
+/// Enumerates every arrangement of the remaining mines that is consistent with
+/// the revealed numbers. Tiles bordering numbers are split into independent
+/// components that are each solved exactly, and the tiles that no number
+/// touches are accounted for combinatorially.
+pub(crate) fn enumerate_frontier<W: Weight>(
+    board: &VisibleBoard,
+    constraints: &[Constraint],
+) -> FrontierEnumeration<W> {
+    let flagged = board
+        .tiles
+        .iter()
+        .filter(|tile| **tile == VisibleTile::Flagged)
+        .count() as i32;
+    let remaining_mines = board.num_mines - flagged;
+
+    let frontier: HashSet<usize> = constraints
+        .iter()
+        .flat_map(|constraint| constraint.cells.iter().copied())
+        .collect();
+    let interior: Vec<usize> = (0..board.tiles.len())
+        .filter(|index| board.tiles[*index] == VisibleTile::Unrevealed)
+        .filter(|index| !frontier.contains(index))
+        .collect();
+
+    if remaining_mines < 0 {
+        let cells = frontier
+            .iter()
+            .chain(interior.iter())
+            .map(|index| CellWeight {
+                index: *index,
+                mine: W::ZERO,
+                safe: W::ZERO,
+                reasons: Vec::new(),
+            })
+            .collect();
+        return FrontierEnumeration {
+            cells,
+            total: W::ZERO,
+        };
+    }
+    let remaining_mines = remaining_mines as usize;
+
+    let components: Vec<ComponentEnumeration<W>> = split_into_components(constraints)
+        .into_iter()
+        .map(|component| enumerate_component(constraints, &component, remaining_mines))
+        .collect();
+
+    // The arrangements of every other component, by number of mines.
+    let mut prefixes = vec![vec![W::ONE]];
+    for component in &components {
+        let next = multiply(prefixes.last().unwrap(), &component.totals, remaining_mines);
+        prefixes.push(next);
+    }
+    let mut suffixes = vec![vec![W::ONE]];
+    for component in components.iter().rev() {
+        let next = multiply(suffixes.last().unwrap(), &component.totals, remaining_mines);
+        suffixes.push(next);
+    }
+    suffixes.reverse();
+
+    // The ways to place whatever is left in the tiles no number touches.
+    let interior_ways = W::binomials(interior.len(), remaining_mines);
+    let interior_weight = |mines: usize| {
+        if mines <= remaining_mines {
+            interior_ways[remaining_mines - mines]
+        } else {
+            W::ZERO
+        }
+    };
+
+    let mut cells = Vec::new();
+    let all_reasons: Vec<usize> = constraints
+        .iter()
+        .map(|constraint| constraint.tile)
+        .collect();
+    for (i, component) in components.iter().enumerate() {
+        let others = multiply(&prefixes[i], &suffixes[i + 1], remaining_mines);
+        // How much an arrangement of this component with a given number of mines
+        // weighs once the rest of the board is taken into account.
+        let outside: Vec<W> = (0..=remaining_mines)
+            .map(|mines| {
+                let mut weight = W::ZERO;
+                for (other_mines, other_weight) in others.iter().enumerate() {
+                    weight = weight.add(other_weight.mul(interior_weight(mines + other_mines)));
+                }
+                weight
+            })
+            .collect();
+
+        for (group, mine, safe) in &component.groups {
+            let mine = dot(mine, &outside);
+            let safe = dot(safe, &outside);
+            for cell in &group.cells {
+                cells.push(CellWeight {
+                    index: *cell,
+                    mine,
+                    safe,
+                    reasons: component.reasons.clone(),
+                });
+            }
+        }
+    }
+
+    let all = prefixes.last().unwrap();
+    let mut total = W::ZERO;
+    let mut interior_mine = W::ZERO;
+    let mut interior_safe = W::ZERO;
+    for (mines, weight) in all.iter().enumerate() {
+        if mines > remaining_mines {
+            break;
+        }
+        let left_over = remaining_mines - mines;
+        let weight = weight.mul(interior_weight(mines));
+        total = total.add(weight);
+        if left_over <= interior.len() {
+            // Picking one interior tile, it is a mine in left_over / interior.len()
+            // of these arrangements. Both sides share the 1 / interior.len() factor.
+            interior_mine = interior_mine.add(weight.mul(W::count(left_over)));
+            interior_safe = interior_safe.add(weight.mul(W::count(interior.len() - left_over)));
+        }
+    }
+    for index in interior {
+        cells.push(CellWeight {
+            index,
+            mine: interior_mine,
+            safe: interior_safe,
+            reasons: all_reasons.clone(),
+        });
+    }
+
+    FrontierEnumeration { cells, total }
+}
+
+/// Splits the numbers into sets that share no unknown tiles.
+fn split_into_components(constraints: &[Constraint]) -> Vec<Vec<usize>> {
+    let mut constraints_by_cell: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, constraint) in constraints.iter().enumerate() {
+        for cell in &constraint.cells {
+            constraints_by_cell.entry(*cell).or_default().push(i);
+        }
+    }
+
+    let mut visited = vec![false; constraints.len()];
+    let mut components = Vec::new();
+    for start in 0..constraints.len() {
+        if visited[start] {
+            continue;
+        }
+
+        // Visiting the numbers breadth first keeps neighboring numbers close
+        // together, which keeps the enumeration small.
+        let mut component = Vec::new();
+        let mut queue = VecDeque::new();
+        visited[start] = true;
+        queue.push_back(start);
+        while let Some(current) = queue.pop_front() {
+            component.push(current);
+            for cell in &constraints[current].cells {
+                for next in &constraints_by_cell[cell] {
+                    if !visited[*next] {
+                        visited[*next] = true;
+                        queue.push_back(*next);
+                    }
+                }
+            }
+        }
+        components.push(component);
+    }
+    components
+}
+
+/// Counts the arrangements of a single component by sweeping over its groups and
+/// keeping track of how many mines each partially visited number has so far.
+fn enumerate_component<W: Weight>(
+    constraints: &[Constraint],
+    component: &[usize],
+    max_mines: usize,
+) -> ComponentEnumeration<W> {
+    // Group the tiles by the numbers that touch them, in the order the numbers
+    // were visited.
+    let mut group_by_key: HashMap<Vec<usize>, usize> = HashMap::new();
+    let mut keys: Vec<Vec<usize>> = Vec::new();
+    let mut group_cells: Vec<Vec<usize>> = Vec::new();
+    let mut cell_constraints: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (local, constraint) in component.iter().enumerate() {
+        for cell in &constraints[*constraint].cells {
+            cell_constraints.entry(*cell).or_default().push(local);
+        }
+    }
+    for constraint in component {
+        for cell in &constraints[*constraint].cells {
+            if let Some(key) = cell_constraints.remove(cell) {
+                let group = *group_by_key.entry(key.clone()).or_insert_with(|| {
+                    keys.push(key);
+                    group_cells.push(Vec::new());
+                    group_cells.len() - 1
+                });
+                group_cells[group].push(*cell);
+            }
+        }
+    }
+
+    let targets: Vec<usize> = component
+        .iter()
+        .map(|constraint| constraints[*constraint].mines as usize)
+        .collect();
+    let mut remaining_capacity: Vec<usize> = component
+        .iter()
+        .map(|constraint| constraints[*constraint].cells.len())
+        .collect();
+    let groups: Vec<Group> = keys
+        .into_iter()
+        .zip(group_cells)
+        .map(|(key, cells)| {
+            let memberships = key
+                .iter()
+                .map(|local| {
+                    remaining_capacity[*local] -= cells.len();
+                    (*local, remaining_capacity[*local])
+                })
+                .collect();
+            Group { cells, memberships }
+        })
+        .collect();
+
+    // Moving past a group with `mines` mines in it. Numbers that have seen all of
+    // their tiles must be satisfied, and are reset so that equal states merge.
+    let advance = |state: &[u16], group: &Group, mines: usize| -> Option<Vec<u16>> {
+        let mut next = state.to_vec();
+        for (local, capacity_after) in &group.memberships {
+            let count = next[*local] as usize + mines;
+            if count > targets[*local] || count + capacity_after < targets[*local] {
+                return None;
+            }
+            next[*local] = if *capacity_after == 0 {
+                0
+            } else {
+                count as u16
+            };
+        }
+        Some(next)
+    };
+
+    let initial = vec![0u16; component.len()];
+    let mut forward: Vec<HashMap<Vec<u16>, Vec<W>>> =
+        vec![HashMap::from([(initial.clone(), vec![W::ONE])])];
+    for group in &groups {
+        let ways = W::binomials(group.cells.len(), group.cells.len());
+        let mut next: HashMap<Vec<u16>, Vec<W>> = HashMap::new();
+        for (state, weights) in forward.last().unwrap() {
+            for (mines, way) in ways.iter().enumerate() {
+                if let Some(next_state) = advance(state, group, mines) {
+                    let target = next.entry(next_state).or_default();
+                    add_shifted(target, weights, mines, *way, max_mines);
+                }
+            }
+        }
+        forward.push(next);
+    }
+
+    let mut backward: HashMap<Vec<u16>, Vec<W>> = HashMap::from([(initial.clone(), vec![W::ONE])]);
+    let mut group_weights = Vec::with_capacity(groups.len());
+    for (position, group) in groups.iter().enumerate().rev() {
+        let size = group.cells.len();
+        let ways = W::binomials(size, size);
+        let mut previous: HashMap<Vec<u16>, Vec<W>> = HashMap::new();
+        let mut mine = Vec::new();
+        let mut safe = Vec::new();
+        for (state, before) in &forward[position] {
+            let mut after_state = Vec::new();
+            for (mines, way) in ways.iter().enumerate() {
+                if let Some(next_state) = advance(state, group, mines) {
+                    if let Some(after) = backward.get(&next_state) {
+                        add_shifted(&mut after_state, after, mines, *way, max_mines);
+                        let through = multiply(before, after, max_mines);
+                        add_shifted(
+                            &mut mine,
+                            &through,
+                            mines,
+                            way.mul(W::count(mines)),
+                            max_mines,
+                        );
+                        add_shifted(
+                            &mut safe,
+                            &through,
+                            mines,
+                            way.mul(W::count(size - mines)),
+                            max_mines,
+                        );
+                    }
+                }
+            }
+            previous.insert(state.clone(), after_state);
+        }
+        group_weights.push((mine, safe));
+        backward = previous;
+    }
+    group_weights.reverse();
+
+    let mut totals = backward.remove(&initial).unwrap_or_default();
+    let normalizer = W::normalizer(&totals);
+    scale(&mut totals, normalizer, max_mines);
+    let groups = groups
+        .into_iter()
+        .zip(group_weights)
+        .map(|(group, (mut mine, mut safe))| {
+            scale(&mut mine, normalizer, max_mines);
+            scale(&mut safe, normalizer, max_mines);
+            (group, mine, safe)
+        })
+        .collect();
+
+    ComponentEnumeration {
+        totals,
+        groups,
+        reasons: component
+            .iter()
+            .map(|constraint| constraints[*constraint].tile)
+            .collect(),
+    }
+}
+
+/// `target += source * x^shift * factor`, dropping anything past `max_mines`.
+fn add_shifted<W: Weight>(
+    target: &mut Vec<W>,
+    source: &[W],
+    shift: usize,
+    factor: W,
+    max_mines: usize,
+) {
+    for (mines, weight) in source.iter().enumerate() {
+        let mines = mines + shift;
+        if mines > max_mines {
+            break;
+        }
+        if target.len() <= mines {
+            target.resize(mines + 1, W::ZERO);
+        }
+        target[mines] = target[mines].add(weight.mul(factor));
+    }
+}
+
+/// Multiplies two polynomials indexed by number of mines.
+fn multiply<W: Weight>(first: &[W], second: &[W], max_mines: usize) -> Vec<W> {
+    let mut result = Vec::new();
+    for (mines, weight) in first.iter().enumerate() {
+        add_shifted(&mut result, second, mines, *weight, max_mines);
+    }
+    result
+}
+
+fn dot<W: Weight>(first: &[W], second: &[W]) -> W {
+    first
+        .iter()
+        .zip(second)
+        .fold(W::ZERO, |sum, (a, b)| sum.add(a.mul(*b)))
+}
+
+fn scale<W: Weight>(values: &mut Vec<W>, factor: W, max_mines: usize) {
+    let original = std::mem::take(values);
+    add_shifted(values, &original, 0, factor, max_mines);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(x: i32, y: i32) -> TileCoordinate {
+        TileCoordinate { x, y }
+    }
+
+    // A board where every tile not listed is still unrevealed.
+    fn visible_board(
+        width: i32,
+        height: i32,
+        num_mines: i32,
+        tiles: &[((i32, i32), VisibleTile)],
+    ) -> VisibleBoard {
+        let mut board = VisibleBoard::new(width, height, num_mines);
+        for ((x, y), visible_tile) in tiles {
+            board.set_tile(&tile(*x, *y), *visible_tile);
+        }
+        board
+    }
+
+    fn deduction(
+        tile: TileCoordinate,
+        is_mine: bool,
+        rule: Rule,
+        reasons: &[TileCoordinate],
+    ) -> Deduction {
+        Deduction {
+            tile,
+            is_mine,
+            rule,
+            reasons: reasons.to_vec(),
+        }
+    }
+
+    #[test]
+    fn single_tile_finds_a_number_that_needs_every_tile() {
+        let board = visible_board(
+            3,
+            1,
+            1,
+            &[
+                ((0, 0), VisibleTile::Revealed(0)),
+                ((1, 0), VisibleTile::Revealed(1)),
+            ],
+        );
+
+        assert_eq!(
+            next_deductions(&board),
+            vec![deduction(tile(2, 0), true, Rule::SingleTile, &[tile(1, 0)])]
+        );
+    }
+
+    #[test]
+    fn single_tile_clears_around_a_number_with_all_its_flags() {
+        let board = visible_board(
+            3,
+            1,
+            1,
+            &[
+                ((0, 0), VisibleTile::Flagged),
+                ((1, 0), VisibleTile::Revealed(1)),
+            ],
+        );
+
+        assert_eq!(
+            next_deductions(&board),
+            vec![deduction(
+                tile(2, 0),
+                false,
+                Rule::SingleTile,
+                &[tile(1, 0)]
+            )]
+        );
+    }
+
+    #[test]
+    fn subset_clears_what_only_the_larger_number_touches() {
+        // Mines on both ends of the top row, so the bottom row reads 1 1 1 1.
+        // Neither number can be settled on its own, but the ends of the row
+        // account for the middle ones.
+        let board = visible_board(
+            4,
+            2,
+            2,
+            &[
+                ((0, 1), VisibleTile::Revealed(1)),
+                ((1, 1), VisibleTile::Revealed(1)),
+                ((2, 1), VisibleTile::Revealed(1)),
+                ((3, 1), VisibleTile::Revealed(1)),
+            ],
+        );
+
+        assert_eq!(
+            next_deductions(&board),
+            vec![
+                deduction(tile(2, 0), false, Rule::Subset, &[tile(1, 1), tile(0, 1)]),
+                deduction(tile(1, 0), false, Rule::Subset, &[tile(2, 1), tile(3, 1)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn enumeration_uses_the_mine_count() {
+        // The only mine is next to the number, so the tiles it doesn't touch
+        // are safe even though the number can't say which of its tiles it is.
+        let board = visible_board(5, 1, 1, &[((2, 0), VisibleTile::Revealed(1))]);
+
+        assert_eq!(
+            next_deductions(&board),
+            vec![
+                deduction(tile(0, 0), false, Rule::Enumeration, &[tile(2, 0)]),
+                deduction(tile(4, 0), false, Rule::Enumeration, &[tile(2, 0)]),
+            ]
+        );
+    }
+
+    // A board with mines on `mines`, where the player has revealed `revealed`
+    // and flagged `flagged`.
+    fn board_from_layout(
+        width: i32,
+        height: i32,
+        mines: &[(i32, i32)],
+        revealed: &[(i32, i32)],
+        flagged: &[(i32, i32)],
+    ) -> VisibleBoard {
+        let mut board = VisibleBoard::new(width, height, mines.len() as i32);
+        for (x, y) in revealed {
+            let index = board.index_helper.compute_index(*x, *y);
+            let count = board
+                .neighbors(index)
+                .filter(|neighbor| {
+                    let neighbor = board.coordinate(*neighbor);
+                    mines.contains(&(neighbor.x, neighbor.y))
+                })
+                .count() as i32;
+            board.set_tile(&tile(*x, *y), VisibleTile::Revealed(count));
+        }
+        for (x, y) in flagged {
+            board.set_tile(&tile(*x, *y), VisibleTile::Flagged);
+        }
+        board
+    }
+
+    // Tries every way of placing the remaining mines on the unknown tiles.
+    fn brute_force_probabilities(board: &VisibleBoard) -> Option<Vec<TileProbability>> {
+        let unknown: Vec<usize> = (0..board.tiles.len())
+            .filter(|index| board.tile_at(*index) == VisibleTile::Unrevealed)
+            .collect();
+        let flagged = (0..board.tiles.len())
+            .filter(|index| board.tile_at(*index) == VisibleTile::Flagged)
+            .count() as u32;
+        let remaining_mines = board.num_mines() as u32 - flagged;
+
+        let mut total = 0u64;
+        let mut mine_counts = vec![0u64; board.tiles.len()];
+        for arrangement in 0u32..(1 << unknown.len()) {
+            if arrangement.count_ones() != remaining_mines {
+                continue;
+            }
+            let is_mine = |index: usize| {
+                board.tile_at(index) == VisibleTile::Flagged
+                    || unknown
+                        .iter()
+                        .position(|cell| *cell == index)
+                        .is_some_and(|bit| arrangement & (1 << bit) != 0)
+            };
+            let consistent = (0..board.tiles.len()).all(|index| match board.tile_at(index) {
+                VisibleTile::Revealed(count) => {
+                    board.neighbors(index).filter(|n| is_mine(*n)).count() as i32 == count
+                }
+                _ => true,
+            });
+            if consistent {
+                total += 1;
+                for (bit, cell) in unknown.iter().enumerate() {
+                    if arrangement & (1 << bit) != 0 {
+                        mine_counts[*cell] += 1;
+                    }
+                }
+            }
+        }
+        if total == 0 {
+            return None;
+        }
+
+        Some(
+            (0..board.tiles.len())
+                .filter_map(|index| {
+                    let probability = match board.tile_at(index) {
+                        VisibleTile::Unrevealed => mine_counts[index] as f64 / total as f64,
+                        VisibleTile::Flagged => 1.0,
+                        VisibleTile::Revealed(_) => return None,
+                    };
+                    Some(TileProbability {
+                        tile: board.coordinate(index),
+                        probability,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn assert_matches_brute_force(board: &VisibleBoard) {
+        let expected = brute_force_probabilities(board);
+        let actual = mine_probabilities(board);
+        match (expected, actual) {
+            (None, None) => {}
+            (Some(expected), Some(actual)) => {
+                assert_eq!(expected.len(), actual.len());
+                for (expected, actual) in expected.iter().zip(&actual) {
+                    assert_eq!(expected.tile, actual.tile);
+                    assert!(
+                        (expected.probability - actual.probability).abs() < 1e-9,
+                        "{:?}: expected {}, got {}",
+                        expected.tile,
+                        expected.probability,
+                        actual.probability
+                    );
+                }
+            }
+            (expected, actual) => panic!("expected {:?}, got {:?}", expected, actual),
+        }
+    }
+
+    #[test]
+    fn probabilities_match_brute_force_on_a_line() {
+        // With one mine the tiles the number doesn't touch are safe, with two
+        // they are as likely as not.
+        for num_mines in 1..=3 {
+            let board = visible_board(5, 1, num_mines, &[((2, 0), VisibleTile::Revealed(1))]);
+            assert_matches_brute_force(&board);
+        }
+    }
+
+    #[test]
+    fn probabilities_match_brute_force_with_interior_tiles() {
+        let board = board_from_layout(
+            4,
+            4,
+            &[(0, 0), (3, 1), (2, 3)],
+            &[(1, 1), (2, 1), (1, 2)],
+            &[],
+        );
+        assert_matches_brute_force(&board);
+
+        let board = board_from_layout(
+            4,
+            4,
+            &[(0, 0), (3, 1), (2, 3), (3, 3)],
+            &[(0, 3), (1, 3), (0, 2), (1, 2), (2, 2)],
+            &[],
+        );
+        assert_matches_brute_force(&board);
+    }
+
+    #[test]
+    fn probabilities_match_brute_force_when_the_mine_count_matters() {
+        // Two separate numbers on the same board compete for the few mines
+        // that are left.
+        let board = board_from_layout(5, 3, &[(0, 0), (4, 2)], &[(0, 2), (4, 0)], &[]);
+        assert_matches_brute_force(&board);
+
+        let board = board_from_layout(
+            5,
+            3,
+            &[(1, 0), (3, 2), (4, 0)],
+            &[(0, 2), (1, 2), (4, 1)],
+            &[(4, 0)],
+        );
+        assert_matches_brute_force(&board);
+    }
+
+    #[test]
+    fn probabilities_match_brute_force_on_a_contradictory_board() {
+        let board = visible_board(5, 1, 0, &[((2, 0), VisibleTile::Revealed(1))]);
+        assert_matches_brute_force(&board);
+    }
+
+    #[test]
+    fn multi_mine_boards_are_left_alone() {
+        let board = visible_board(
+            3,
+            1,
+            1,
+            &[
+                ((0, 0), VisibleTile::Revealed(0)),
+                ((1, 0), VisibleTile::Revealed(1)),
+            ],
+        )
+        .with_max_mines_per_tile(2);
+
+        assert!(next_deductions(&board).is_empty());
+        assert_eq!(mine_probabilities(&board), None);
+    }
+
+    #[test]
+    fn nothing_is_deduced_when_a_guess_is_needed() {
+        let board = visible_board(5, 1, 2, &[((2, 0), VisibleTile::Revealed(1))]);
+
+        assert!(next_deductions(&board).is_empty());
+    }
+
+    #[test]
+    fn nothing_is_deduced_on_a_contradictory_board() {
+        let board = visible_board(
+            3,
+            1,
+            1,
+            &[
+                ((0, 0), VisibleTile::Flagged),
+                ((1, 0), VisibleTile::Revealed(0)),
+            ],
+        );
+
+        assert!(next_deductions(&board).is_empty());
+    }
+}
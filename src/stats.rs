@@ -0,0 +1,341 @@
+use crate::board::{BoardConfig, IndexHelper, MineState};
+use crate::topology::Topology;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The Bechtel's Board Benchmark Value of a board: the fewest left clicks it
+/// takes to clear it. Every opening takes one click, and so does every
+/// numbered tile that isn't on the edge of an opening.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct ThreeBv {
+    pub total: u32,
+    /// How much of the total has been cleared so far.
+    pub solved: u32,
+}
+
+/// Computes the 3BV of a board from how many mines each tile holds and how
+/// many are around it.
+pub fn three_bv(
+    config: &BoardConfig,
+    topology: Topology,
+    mines: &[i32],
+    neighbor_counts: &[i32],
+    mine_states: &[MineState],
+) -> ThreeBv {
+    let index_helper = IndexHelper::new(config.width(), config.height());
+    let neighbors = |index: usize| topology.neighbors(&index_helper, index);
+    let is_revealed = |index: usize| mine_states[index] == MineState::Revealed;
+    let is_opening = |index: usize| mines[index] == 0 && neighbor_counts[index] == 0;
+    let is_number = |index: usize| mines[index] == 0 && neighbor_counts[index] > 0;
+
+    let mut result = ThreeBv::default();
+    let mut visited = vec![false; neighbor_counts.len()];
+    let mut borders_opening = vec![false; neighbor_counts.len()];
+
+    // Openings are flood filled from any of their empty tiles, so one revealed
+    // tile means the whole opening has been cleared.
+    for start in 0..neighbor_counts.len() {
+        if !is_opening(start) || visited[start] {
+            continue;
+        }
+
+        result.total += 1;
+        if is_revealed(start) {
+            result.solved += 1;
+        }
+
+        visited[start] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(index) = queue.pop_front() {
+            for neighbor in neighbors(index) {
+                if is_opening(neighbor) {
+                    if !visited[neighbor] {
+                        visited[neighbor] = true;
+                        queue.push_back(neighbor);
+                    }
+                } else {
+                    borders_opening[neighbor] = true;
+                }
+            }
+        }
+    }
+
+    for (index, borders_opening) in borders_opening.into_iter().enumerate() {
+        if is_number(index) && !borders_opening {
+            result.total += 1;
+            if is_revealed(index) {
+                result.solved += 1;
+            }
+        }
+    }
+
+    result
+}
+
+/// The clicks that reached the board during a game.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct ClickCounts {
+    pub left: u32,
+    pub right: u32,
+    pub chord: u32,
+}
+
+impl ClickCounts {
+    pub fn total(&self) -> u32 {
+        self.left + self.right + self.chord
+    }
+}
+
+/// How a finished game went, in the numbers players compare.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct GameStats {
+    pub won: bool,
+    pub time: Duration,
+    pub three_bv: ThreeBv,
+    pub clicks: ClickCounts,
+    /// How many mines were hit, including the one that ended a lost game.
+    pub lives_used: u32,
+}
+
+impl GameStats {
+    /// Solved 3BV per second.
+    pub fn three_bv_per_second(&self) -> Option<f64> {
+        let seconds = self.time.as_secs_f64();
+        if seconds > 0.0 {
+            Some(self.three_bv.solved as f64 / seconds)
+        } else {
+            None
+        }
+    }
+
+    /// Solved 3BV per click, as a fraction. Anything above 1.0 means fewer
+    /// clicks were needed than the 3BV suggests, thanks to chording.
+    pub fn efficiency(&self) -> Option<f64> {
+        match self.clicks.total() {
+            0 => None,
+            clicks => Some(self.three_bv.solved as f64 / clicks as f64),
+        }
+    }
+
+    /// Index of Speed: log(3BV) / log(time in seconds). Only meaningful for
+    /// games that took more than a second.
+    pub fn ios(&self) -> Option<f64> {
+        let seconds = self.time.as_secs_f64();
+        if seconds > 1.0 && self.three_bv.solved > 0 {
+            Some((self.three_bv.solved as f64).ln() / seconds.ln())
+        } else {
+            None
+        }
+    }
+
+    /// Rapport Qualité Prix: time / (3BV/s). Lower is better.
+    pub fn rqp(&self) -> Option<f64> {
+        self.three_bv_per_second()
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| self.time.as_secs_f64() / rate)
+    }
+}
+
+impl fmt::Display for GameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let optional = |value: Option<f64>, precision: usize| match value {
+            Some(value) => format!("{:.*}", precision, value),
+            None => "-".to_owned(),
+        };
+
+        write!(
+            f,
+            "{} in {:.2}s, 3BV {}/{}, 3BV/s {}, clicks {}+{}+{}, efficiency {}%, IOS {}, RQP {}",
+            if self.won { "Won" } else { "Lost" },
+            self.time.as_secs_f64(),
+            self.three_bv.solved,
+            self.three_bv.total,
+            optional(self.three_bv_per_second(), 2),
+            self.clicks.left,
+            self.clicks.right,
+            self.clicks.chord,
+            optional(self.efficiency().map(|efficiency| efficiency * 100.0), 0),
+            optional(self.ios(), 3),
+            optional(self.rqp(), 2),
+        )?;
+        match self.lives_used {
+            0 => Ok(()),
+            1 => write!(f, ", 1 life used"),
+            lives_used => write!(f, ", {} lives used", lives_used),
+        }
+    }
+}
+
+/// Only counts the time spent playing, so it starts with the first click
+/// rather than when the board appears.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Stopwatch {
+    elapsed: Duration,
+    started: Option<Instant>,
+}
+
+impl Stopwatch {
+    pub fn with_elapsed(elapsed: Duration) -> Self {
+        Self {
+            elapsed,
+            started: None,
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.elapsed = self.elapsed();
+        self.started = None;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match self.started {
+            Some(started) => self.elapsed + started.elapsed(),
+            None => self.elapsed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A single row with mines on the third and fifth tiles:
+    //
+    //   0 1 * 2 * 1 0
+    //
+    // The two zeros are openings of their own, and the ones next to them are
+    // cleared along with them. The two in the middle is the only number that
+    // needs a click of its own, so the 3BV is 3.
+    const MINES: [i32; 7] = [0, 0, 1, 0, 1, 0, 0];
+    const NEIGHBOR_COUNTS: [i32; 7] = [0, 1, 0, 2, 0, 1, 0];
+
+    fn row_three_bv(revealed: &[usize]) -> ThreeBv {
+        let mut mine_states = [MineState::Empty; 7];
+        for index in revealed {
+            mine_states[*index] = MineState::Revealed;
+        }
+        three_bv(
+            &BoardConfig::custom(7, 1, 2).unwrap(),
+            Topology::default(),
+            &MINES,
+            &NEIGHBOR_COUNTS,
+            &mine_states,
+        )
+    }
+
+    #[test]
+    fn three_bv_counts_openings_and_lone_numbers() {
+        assert_eq!(
+            row_three_bv(&[]),
+            ThreeBv {
+                total: 3,
+                solved: 0
+            }
+        );
+        // Sweeping the first zero clears its opening.
+        assert_eq!(
+            row_three_bv(&[0, 1]),
+            ThreeBv {
+                total: 3,
+                solved: 1
+            }
+        );
+        // A number on the edge of an opening doesn't count on its own.
+        assert_eq!(
+            row_three_bv(&[5]),
+            ThreeBv {
+                total: 3,
+                solved: 0
+            }
+        );
+        assert_eq!(
+            row_three_bv(&[0, 1, 3, 5, 6]),
+            ThreeBv {
+                total: 3,
+                solved: 3
+            }
+        );
+    }
+
+    fn stats(solved: u32, time: Duration, clicks: ClickCounts) -> GameStats {
+        GameStats {
+            won: true,
+            time,
+            three_bv: ThreeBv { total: 15, solved },
+            clicks,
+            lives_used: 0,
+        }
+    }
+
+    const CLICKS: ClickCounts = ClickCounts {
+        left: 10,
+        right: 2,
+        chord: 3,
+    };
+
+    #[test]
+    fn rates_a_finished_game() {
+        let stats = stats(12, Duration::from_secs(8), CLICKS);
+
+        let close = |value: Option<f64>, expected: f64| (value.unwrap() - expected).abs() < 1e-9;
+        // 12 3BV in 8 seconds.
+        assert!(close(stats.three_bv_per_second(), 1.5));
+        // 12 3BV in 15 clicks.
+        assert!(close(stats.efficiency(), 0.8));
+        // ln 12 / ln 8
+        assert!(close(stats.ios(), 1.194_987_500_867_595));
+        // 8 seconds / 1.5 3BV/s
+        assert!(close(stats.rqp(), 16.0 / 3.0));
+
+        assert_eq!(
+            stats.to_string(),
+            "Won in 8.00s, 3BV 12/15, 3BV/s 1.50, clicks 10+2+3, efficiency 80%, IOS 1.195, RQP 5.33"
+        );
+    }
+
+    #[test]
+    fn leaves_out_rates_that_mean_nothing() {
+        let instant = stats(12, Duration::ZERO, ClickCounts::default());
+        assert_eq!(instant.three_bv_per_second(), None);
+        assert_eq!(instant.efficiency(), None);
+        assert_eq!(instant.ios(), None);
+        assert_eq!(instant.rqp(), None);
+
+        // The log of a second or less is zero or below.
+        assert_eq!(stats(12, Duration::from_secs(1), CLICKS).ios(), None);
+        // Nothing solved takes forever at 0 3BV/s.
+        assert_eq!(stats(0, Duration::from_secs(8), CLICKS).rqp(), None);
+
+        let lost = GameStats {
+            won: false,
+            lives_used: 2,
+            ..instant
+        };
+        assert_eq!(
+            lost.to_string(),
+            "Lost in 0.00s, 3BV 12/15, 3BV/s -, clicks 0+0+0, efficiency -%, IOS -, RQP -, 2 lives used"
+        );
+    }
+
+    #[test]
+    fn stopwatch_only_runs_while_started() {
+        let mut stopwatch = Stopwatch::with_elapsed(Duration::from_secs(5));
+        assert_eq!(stopwatch.elapsed(), Duration::from_secs(5));
+
+        stopwatch.start();
+        std::thread::sleep(Duration::from_millis(5));
+        stopwatch.stop();
+        let stopped_at = stopwatch.elapsed();
+        assert!(stopped_at >= Duration::from_millis(5005));
+
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(stopwatch.elapsed(), stopped_at);
+    }
+}
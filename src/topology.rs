@@ -0,0 +1,476 @@
+use crate::board::{BoardConfig, IndexHelper};
+use std::fmt;
+use std::str::FromStr;
+
+const SQUARE_OFFSETS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+// Odd rows are shifted right by half a tile, so which tiles above and below
+// touch a tile depends on the row it's in.
+const HEX_EVEN_ROW_OFFSETS: [(i32, i32); 6] = [(-1, -1), (0, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)];
+const HEX_ODD_ROW_OFFSETS: [(i32, i32); 6] = [(0, -1), (1, -1), (-1, 0), (1, 0), (0, 1), (1, 1)];
+
+/// The shape of the tiles.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub enum TileShape {
+    /// Square tiles, each touching up to eight others.
+    #[default]
+    Square,
+    /// Hexagonal tiles in rows, with every odd row shifted right by half a
+    /// tile. Each tile touches up to six others.
+    Hex,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum NeighborhoodError {
+    Empty,
+    InvalidOffset { x: i32, y: i32 },
+    NotSymmetric { x: i32, y: i32 },
+    Unreadable(String),
+}
+
+impl fmt::Display for NeighborhoodError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NeighborhoodError::Empty => write!(f, "a neighborhood needs at least one offset"),
+            NeighborhoodError::InvalidOffset { x, y } => write!(
+                f,
+                "{},{} is not a valid offset, offsets have to be within {} tiles and not 0,0",
+                x,
+                y,
+                Neighborhood::MAX_DISTANCE
+            ),
+            NeighborhoodError::NotSymmetric { x, y } => write!(
+                f,
+                "the neighborhood has {},{} but not {},{}, neighbors have to go both ways",
+                x, y, -x, -y
+            ),
+            NeighborhoodError::Unreadable(text) => {
+                write!(
+                    f,
+                    "expected a list of \"<x>,<y>\" offsets, found \"{}\"",
+                    text
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for NeighborhoodError {}
+
+/// The offsets from a tile to the tiles that count as its neighbors, for
+/// variants where those aren't just the tiles around it.
+///
+/// A tile is always a neighbor of its neighbors, so that an opening cleared
+/// from any of its tiles is the same opening.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Neighborhood {
+    offsets: [(i32, i32); Neighborhood::MAX_OFFSETS],
+    len: usize,
+}
+
+impl Neighborhood {
+    /// How far away from a tile its neighbors can be, in either direction.
+    pub const MAX_DISTANCE: i32 = 3;
+    pub const MAX_OFFSETS: usize =
+        ((2 * Self::MAX_DISTANCE + 1) * (2 * Self::MAX_DISTANCE + 1) - 1) as usize;
+
+    /// Builds a neighborhood from a list of offsets, ignoring repeats.
+    pub fn new(offsets: &[(i32, i32)]) -> Result<Self, NeighborhoodError> {
+        let mut result = Self {
+            offsets: [(0, 0); Self::MAX_OFFSETS],
+            len: 0,
+        };
+        for &(x, y) in offsets {
+            let in_range = |value: i32| value.abs() <= Self::MAX_DISTANCE;
+            if (x, y) == (0, 0) || !in_range(x) || !in_range(y) {
+                return Err(NeighborhoodError::InvalidOffset { x, y });
+            }
+            if !result.offsets().contains(&(x, y)) {
+                result.offsets[result.len] = (x, y);
+                result.len += 1;
+            }
+        }
+
+        if result.len == 0 {
+            return Err(NeighborhoodError::Empty);
+        }
+        if let Some(&(x, y)) = result
+            .offsets()
+            .iter()
+            .find(|(x, y)| !result.offsets().contains(&(-x, -y)))
+        {
+            return Err(NeighborhoodError::NotSymmetric { x, y });
+        }
+
+        Ok(result)
+    }
+
+    /// The four tiles that share an edge with the tile.
+    pub fn orthogonal() -> Self {
+        Self::new(&[(0, -1), (-1, 0), (1, 0), (0, 1)]).unwrap()
+    }
+
+    /// The eight tiles a knight could move to from the tile.
+    pub fn knight() -> Self {
+        Self::new(&[
+            (-1, -2),
+            (1, -2),
+            (-2, -1),
+            (2, -1),
+            (-2, 1),
+            (2, 1),
+            (-1, 2),
+            (1, 2),
+        ])
+        .unwrap()
+    }
+
+    /// Every tile up to `radius` tiles away in both directions.
+    pub fn radius(radius: i32) -> Result<Self, NeighborhoodError> {
+        let offsets: Vec<(i32, i32)> = (-radius..=radius)
+            .flat_map(|y| (-radius..=radius).map(move |x| (x, y)))
+            .filter(|offset| *offset != (0, 0))
+            .collect();
+        Self::new(&offsets)
+    }
+
+    pub fn offsets(&self) -> &[(i32, i32)] {
+        &self.offsets[..self.len]
+    }
+}
+
+impl fmt::Debug for Neighborhood {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Neighborhood")
+            .field(&self.offsets())
+            .finish()
+    }
+}
+
+/// Written as space separated "<x>,<y>" offsets.
+impl fmt::Display for Neighborhood {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offsets: Vec<String> = self
+            .offsets()
+            .iter()
+            .map(|(x, y)| format!("{},{}", x, y))
+            .collect();
+        write!(f, "{}", offsets.join(" "))
+    }
+}
+
+/// Reads what `Display` writes, or the name of one of the common
+/// neighborhoods: "orthogonal", "knight", "radius-2" or "radius-3".
+impl FromStr for Neighborhood {
+    type Err = NeighborhoodError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "orthogonal" => return Ok(Self::orthogonal()),
+            "knight" => return Ok(Self::knight()),
+            "radius-2" => return Self::radius(2),
+            "radius-3" => return Self::radius(3),
+            _ => {}
+        }
+
+        let unreadable = || NeighborhoodError::Unreadable(text.to_owned());
+        let offsets = text
+            .split_whitespace()
+            .map(|offset| {
+                let (x, y) = offset.split_once(',').ok_or_else(unreadable)?;
+                Ok((
+                    x.parse().map_err(|_| unreadable())?,
+                    y.parse().map_err(|_| unreadable())?,
+                ))
+            })
+            .collect::<Result<Vec<(i32, i32)>, NeighborhoodError>>()?;
+        Self::new(&offsets)
+    }
+}
+
+/// Which tiles of a board neighbor each other. Everything that looks at the
+/// tiles around another one, from counting mines to flood filling, goes
+/// through here.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Topology {
+    pub shape: TileShape,
+    /// Whether the board wraps around at the edges, so that the first and
+    /// last columns touch, and so do the first and last rows.
+    pub wraps: bool,
+    /// Replaces the tiles around each tile as its neighbors. Only square
+    /// tiles can have one.
+    pub neighborhood: Option<Neighborhood>,
+}
+
+impl Topology {
+    pub const fn new(shape: TileShape, wraps: bool) -> Self {
+        Self {
+            shape,
+            wraps,
+            neighborhood: None,
+        }
+    }
+
+    /// Whether odd rows are drawn half a tile to the right of even ones.
+    pub fn has_shifted_rows(&self) -> bool {
+        self.shape == TileShape::Hex
+    }
+
+    /// Whether a board of the given size can use this topology. Shifted rows
+    /// only line up across the top and bottom edges when there's an even
+    /// number of them, and the offsets of a neighborhood are only meaningful
+    /// on square tiles.
+    pub fn fits(&self, config: &BoardConfig) -> bool {
+        if self.has_shifted_rows() {
+            self.neighborhood.is_none() && !(self.wraps && config.height() % 2 != 0)
+        } else {
+            true
+        }
+    }
+
+    /// The most neighbors any single tile of a board of the given size has.
+    pub fn max_neighbors(&self, config: &BoardConfig) -> usize {
+        let index_helper = IndexHelper::new(config.width(), config.height());
+        (0..config.tile_count())
+            .map(|index| self.neighbors(&index_helper, index).count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn offsets(&self, y: i32) -> &[(i32, i32)] {
+        if let Some(neighborhood) = &self.neighborhood {
+            return neighborhood.offsets();
+        }
+        match self.shape {
+            TileShape::Square => &SQUARE_OFFSETS,
+            TileShape::Hex if y % 2 == 0 => &HEX_EVEN_ROW_OFFSETS,
+            TileShape::Hex => &HEX_ODD_ROW_OFFSETS,
+        }
+    }
+
+    /// The indices of the tiles next to the tile at `index`, each listed once.
+    pub fn neighbors(
+        &self,
+        index_helper: &IndexHelper,
+        index: usize,
+    ) -> impl Iterator<Item = usize> {
+        let x = index_helper.compute_x_from_index(index);
+        let y = index_helper.compute_y_from_index(index);
+
+        let mut result = Neighbors {
+            indices: [0; Neighborhood::MAX_OFFSETS],
+            len: 0,
+            next: 0,
+        };
+        for (offset_x, offset_y) in self.offsets(y) {
+            let (mut x, mut y) = (x + offset_x, y + offset_y);
+            if self.wraps {
+                x = x.rem_euclid(index_helper.width());
+                y = y.rem_euclid(index_helper.height());
+            }
+            if !index_helper.is_in_bounds(x, y) {
+                continue;
+            }
+            // On narrow boards that wrap, both sides can lead to the same
+            // tile, or back to the tile itself.
+            let neighbor = index_helper.compute_index(x, y);
+            if neighbor != index && !result.indices[..result.len].contains(&neighbor) {
+                result.indices[result.len] = neighbor;
+                result.len += 1;
+            }
+        }
+        result
+    }
+}
+
+// The neighbors of a tile, kept on the stack since they're looked up for
+// every tile of a flood fill.
+struct Neighbors {
+    indices: [usize; Neighborhood::MAX_OFFSETS],
+    len: usize,
+    next: usize,
+}
+
+impl Iterator for Neighbors {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.next == self.len {
+            return None;
+        }
+        self.next += 1;
+        Some(self.indices[self.next - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(width: i32, height: i32) -> BoardConfig {
+        BoardConfig::custom(width, height, 1).unwrap()
+    }
+
+    // The neighbors of a tile as coordinates, in a stable order.
+    fn neighbors(topology: Topology, width: i32, height: i32, x: i32, y: i32) -> Vec<(i32, i32)> {
+        let index_helper = IndexHelper::new(width, height);
+        let mut result: Vec<(i32, i32)> = topology
+            .neighbors(&index_helper, index_helper.compute_index(x, y))
+            .map(|index| {
+                (
+                    index_helper.compute_x_from_index(index),
+                    index_helper.compute_y_from_index(index),
+                )
+            })
+            .collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn hex_neighbors_depend_on_the_row() {
+        let hex = Topology::new(TileShape::Hex, false);
+        // Odd rows are shifted right, so they touch the tile above and the
+        // one to its right.
+        assert_eq!(
+            neighbors(hex, 4, 4, 1, 1),
+            vec![(0, 1), (1, 0), (1, 2), (2, 0), (2, 1), (2, 2)]
+        );
+        // Even rows touch the tile above and the one to its left.
+        assert_eq!(
+            neighbors(hex, 4, 4, 1, 2),
+            vec![(0, 1), (0, 2), (0, 3), (1, 1), (1, 3), (2, 2)]
+        );
+        assert_eq!(neighbors(hex, 4, 4, 0, 0), vec![(0, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn wrapping_connects_the_edges() {
+        let wrap = Topology::new(TileShape::Square, true);
+        assert_eq!(
+            neighbors(wrap, 4, 4, 0, 0),
+            vec![
+                (0, 1),
+                (0, 3),
+                (1, 0),
+                (1, 1),
+                (1, 3),
+                (3, 0),
+                (3, 1),
+                (3, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn wrapping_lists_each_neighbor_once() {
+        let wrap = Topology::new(TileShape::Square, true);
+        // Going left and right lead to the same tile, and going up and down
+        // lead back to the tile itself.
+        assert_eq!(neighbors(wrap, 2, 1, 0, 0), vec![(1, 0)]);
+        assert_eq!(neighbors(wrap, 2, 2, 0, 0), vec![(0, 1), (1, 0), (1, 1)]);
+        assert_eq!(neighbors(wrap, 1, 1, 0, 0), vec![]);
+    }
+
+    #[test]
+    fn hex_boards_only_wrap_with_an_even_height() {
+        let hex_wrap = Topology::new(TileShape::Hex, true);
+        assert!(hex_wrap.fits(&config(4, 4)));
+        assert!(!hex_wrap.fits(&config(4, 3)));
+        assert!(Topology::new(TileShape::Hex, false).fits(&config(4, 3)));
+        assert!(Topology::new(TileShape::Square, true).fits(&config(4, 3)));
+
+        let hex_knight = Topology {
+            neighborhood: Some(Neighborhood::knight()),
+            ..Topology::new(TileShape::Hex, false)
+        };
+        assert!(!hex_knight.fits(&config(4, 4)));
+    }
+
+    #[test]
+    fn neighborhoods_reject_bad_offsets() {
+        assert_eq!(
+            Neighborhood::new(&[(1, 0)]),
+            Err(NeighborhoodError::NotSymmetric { x: 1, y: 0 })
+        );
+        assert_eq!(
+            Neighborhood::new(&[(1, 0), (-1, 0), (0, 0)]),
+            Err(NeighborhoodError::InvalidOffset { x: 0, y: 0 })
+        );
+        assert_eq!(
+            Neighborhood::new(&[(4, 0), (-4, 0)]),
+            Err(NeighborhoodError::InvalidOffset { x: 4, y: 0 })
+        );
+        assert_eq!(
+            Neighborhood::radius(4),
+            Err(NeighborhoodError::InvalidOffset { x: -4, y: -4 })
+        );
+        assert_eq!(Neighborhood::new(&[]), Err(NeighborhoodError::Empty));
+    }
+
+    #[test]
+    fn neighborhoods_are_read_from_text() {
+        assert_eq!("knight".parse(), Ok(Neighborhood::knight()));
+        assert_eq!(
+            "1,0 -1,0 1,0".parse(),
+            Neighborhood::new(&[(1, 0), (-1, 0)])
+        );
+        assert_eq!(
+            Neighborhood::knight().to_string().parse(),
+            Ok(Neighborhood::knight())
+        );
+        assert_eq!(
+            "1,0 -1,0 0,0".parse::<Neighborhood>(),
+            Err(NeighborhoodError::InvalidOffset { x: 0, y: 0 })
+        );
+        assert_eq!(
+            "1,0 2,1".parse::<Neighborhood>(),
+            Err(NeighborhoodError::NotSymmetric { x: 1, y: 0 })
+        );
+        assert_eq!(
+            "radius-4".parse::<Neighborhood>(),
+            Err(NeighborhoodError::Unreadable("radius-4".to_owned()))
+        );
+        assert_eq!(
+            "1;0".parse::<Neighborhood>(),
+            Err(NeighborhoodError::Unreadable("1;0".to_owned()))
+        );
+    }
+
+    #[test]
+    fn max_neighbors_is_the_largest_neighborhood_on_the_board() {
+        let square = Topology::default();
+        assert_eq!(square.max_neighbors(&config(9, 9)), 8);
+        assert_eq!(square.max_neighbors(&config(2, 1)), 1);
+        assert_eq!(square.max_neighbors(&config(2, 2)), 3);
+
+        let hex = Topology::new(TileShape::Hex, false);
+        assert_eq!(hex.max_neighbors(&config(4, 4)), 6);
+
+        let wrap = Topology::new(TileShape::Square, true);
+        assert_eq!(wrap.max_neighbors(&config(2, 2)), 3);
+        assert_eq!(wrap.max_neighbors(&config(3, 3)), 8);
+
+        let knight = Topology {
+            neighborhood: Some(Neighborhood::knight()),
+            ..Topology::default()
+        };
+        assert_eq!(knight.max_neighbors(&config(9, 9)), 8);
+        assert_eq!(knight.max_neighbors(&config(2, 2)), 0);
+
+        let radius_3 = Topology {
+            neighborhood: Some(Neighborhood::radius(3).unwrap()),
+            ..Topology::default()
+        };
+        assert_eq!(radius_3.max_neighbors(&config(9, 9)), 48);
+    }
+}
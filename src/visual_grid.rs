@@ -1,5 +1,7 @@
-use crate::minesweeper::IndexHelper;
-use crate::numerics::FromVector2;
+use crate::numerics::{FromPoint, FromVector2, ToPoint};
+use minesweeper_rs::board::TileCoordinate;
+use minesweeper_rs::layout::GridLayout;
+use minesweeper_rs::topology::Topology;
 use windows::{
     core::Result,
     Graphics::SizeInt32,
@@ -10,24 +12,13 @@ use windows::{
 };
 use windows_numerics::{Vector2, Vector3};
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug)]
-pub struct TileCoordinate {
-    pub x: i32,
-    pub y: i32,
-}
-
 pub struct VisualGrid {
     compositor: Compositor,
     root: ContainerVisual,
 
     tiles: Vec<SpriteVisual>,
     selection_visual: SpriteVisual,
-    index_helper: IndexHelper,
-
-    grid_width_in_tiles: i32,
-    grid_height_in_tiles: i32,
-    tile_size: Vector2,
-    margin: Vector2,
+    layout: GridLayout,
 
     current_selection: Option<TileCoordinate>,
 }
@@ -59,50 +50,46 @@ impl VisualGrid {
 
             tiles: Vec::new(),
             selection_visual,
-            index_helper: IndexHelper::new(grid_size_in_tiles.Width, grid_size_in_tiles.Height),
-
-            grid_width_in_tiles: grid_size_in_tiles.Width,
-            grid_height_in_tiles: grid_size_in_tiles.Height,
-            tile_size: *tile_size,
-            margin: *margin,
+            layout: GridLayout::new(
+                grid_size_in_tiles.Width,
+                grid_size_in_tiles.Height,
+                Topology::default(),
+                tile_size.to_point(),
+                margin.to_point(),
+            ),
 
             current_selection: None,
         };
 
-        result.reset(grid_size_in_tiles)?;
+        result.reset(grid_size_in_tiles, Topology::default())?;
 
         Ok(result)
     }
 
-    pub fn reset(&mut self, grid_size_in_tiles: &SizeInt32) -> Result<()> {
+    pub fn reset(&mut self, grid_size_in_tiles: &SizeInt32, topology: Topology) -> Result<()> {
         let children = self.root.Children()?;
         children.RemoveAll()?;
         self.tiles.clear();
 
-        self.index_helper = IndexHelper::new(grid_size_in_tiles.Width, grid_size_in_tiles.Height);
-
-        self.grid_width_in_tiles = grid_size_in_tiles.Width;
-        self.grid_height_in_tiles = grid_size_in_tiles.Height;
+        self.layout = GridLayout::new(
+            grid_size_in_tiles.Width,
+            grid_size_in_tiles.Height,
+            topology,
+            self.layout.tile_size(),
+            self.layout.margin(),
+        );
         self.select_tile(None)?;
 
-        self.root.SetSize(
-            (self.tile_size + self.margin)
-                * Vector2::new(
-                    self.grid_width_in_tiles as f32,
-                    self.grid_height_in_tiles as f32,
-                ),
-        )?;
+        self.root.SetSize(Vector2::from_point(self.layout.size()))?;
 
-        for x in 0..self.grid_width_in_tiles {
-            for y in 0..self.grid_height_in_tiles {
+        let tile_size = Vector2::from_point(self.layout.tile_size());
+        for x in 0..grid_size_in_tiles.Width {
+            for y in 0..grid_size_in_tiles.Height {
+                let offset = self.layout.tile_offset(&TileCoordinate { x, y });
                 let visual = self.compositor.CreateSpriteVisual()?;
-                visual.SetSize(self.tile_size)?;
-                visual.SetCenterPoint(Vector3::from_vector2(&self.tile_size / 2.0, 0.0))?;
-                visual.SetOffset(Vector3::from_vector2(
-                    (&self.margin / 2.0)
-                        + ((self.tile_size + self.margin) * Vector2::new(x as f32, y as f32)),
-                    0.0,
-                ))?;
+                visual.SetSize(tile_size)?;
+                visual.SetCenterPoint(Vector3::from_vector2(&tile_size / 2.0, 0.0))?;
+                visual.SetOffset(Vector3::from_vector2(Vector2::from_point(offset), 0.0))?;
 
                 children.InsertAtTop(&visual)?;
                 self.tiles.push(visual);
@@ -124,24 +111,14 @@ impl VisualGrid {
         &self.selection_visual
     }
 
-    pub fn size(&self) -> Result<Vector2> {
-        self.root.Size()
-    }
-
-    pub fn hit_test(&self, point: &Vector2) -> Option<TileCoordinate> {
-        let x = (point.X / (self.tile_size.X + self.margin.X)) as i32;
-        let y = (point.Y / (self.tile_size.Y + self.margin.Y)) as i32;
-
-        if self.index_helper.is_in_bounds(x, y) {
-            Some(TileCoordinate { x, y })
-        } else {
-            None
-        }
+    pub fn layout(&self) -> &GridLayout {
+        &self.layout
     }
 
     pub fn get_tile(&self, x: i32, y: i32) -> Option<&SpriteVisual> {
-        if self.index_helper.is_in_bounds(x, y) {
-            Some(&self.tiles[self.index_helper.compute_index(x, y)])
+        let index_helper = self.layout.index_helper();
+        if index_helper.is_in_bounds(x, y) {
+            Some(&self.tiles[index_helper.compute_index(x, y)])
         } else {
             None
         }
@@ -151,7 +128,8 @@ impl VisualGrid {
         self.current_selection = tile_coordinate;
         if let Some(tile_coordinate) = tile_coordinate {
             let visual = &self.tiles[self
-                .index_helper
+                .layout
+                .index_helper()
                 .compute_index(tile_coordinate.x, tile_coordinate.y)];
             self.selection_visual.SetParentForTransform(visual)?;
             self.selection_visual.SetIsVisible(true)?;
@@ -1,3 +1,4 @@
+use std::sync::mpsc::{self, Receiver};
 use std::sync::Once;
 use windows::{
     core::{w, Interface, Result, HSTRING, PCWSTR},
@@ -5,12 +6,17 @@ use windows::{
     Win32::{
         Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, RECT, WPARAM},
         System::{LibraryLoader::GetModuleHandleW, WinRT::Composition::ICompositorDesktopInterop},
+        UI::Input::KeyboardAndMouse::{
+            GetKeyState, VK_CONTROL, VK_DOWN, VK_ESCAPE, VK_F1, VK_F24, VK_LEFT, VK_RETURN,
+            VK_RIGHT, VK_UP,
+        },
         UI::WindowsAndMessaging::{
-            AdjustWindowRectEx, CreateWindowExW, DefWindowProcW, GetClientRect, GetWindowLongPtrW,
-            LoadCursorW, PostQuitMessage, RegisterClassW, SetWindowLongPtrW, ShowWindow,
-            CREATESTRUCTW, CW_USEDEFAULT, GWLP_USERDATA, IDC_ARROW, SW_SHOW, WM_DESTROY,
-            WM_LBUTTONDOWN, WM_MOUSEMOVE, WM_NCCREATE, WM_RBUTTONDOWN, WM_SIZE, WM_SIZING,
-            WNDCLASSW, WS_EX_NOREDIRECTIONBITMAP, WS_OVERLAPPEDWINDOW,
+            AdjustWindowRectEx, CreateWindowExW, DefWindowProcW, DestroyWindow, GetClientRect,
+            GetWindowLongPtrW, KillTimer, LoadCursorW, PostQuitMessage, RegisterClassW, SetTimer,
+            SetWindowLongPtrW, SetWindowTextW, ShowWindow, CREATESTRUCTW, CW_USEDEFAULT,
+            GWLP_USERDATA, IDC_ARROW, SW_SHOW, WM_DESTROY, WM_KEYDOWN, WM_LBUTTONDOWN,
+            WM_MOUSEMOVE, WM_NCCREATE, WM_RBUTTONDOWN, WM_SIZE, WM_SIZING, WM_TIMER, WNDCLASSW,
+            WS_EX_NOREDIRECTIONBITMAP, WS_OVERLAPPEDWINDOW,
         },
     },
     UI::Composition::{Compositor, Desktop::DesktopWindowTarget},
@@ -18,17 +24,30 @@ use windows::{
 use windows_numerics::Vector2;
 
 use crate::minesweeper::Minesweeper;
+use minesweeper_rs::events::GameEvent;
+use minesweeper_rs::input::{Command, Key, KeyBindings};
+use minesweeper_rs::mbf;
+use minesweeper_rs::records::{self, Records};
+use minesweeper_rs::replay;
+use minesweeper_rs::save;
 
 static REGISTER_WINDOW_CLASS: Once = Once::new();
 const WINDOW_CLASS_NAME: PCWSTR = w!("minesweeper-rs.Window");
+const REPLAY_TIMER_ID: usize = 1;
+const REPLAY_TIMER_INTERVAL_MS: u32 = 15;
 
 pub struct Window {
     handle: HWND,
+    title: String,
     game: Minesweeper,
+    events: Receiver<GameEvent>,
+    key_bindings: KeyBindings,
+    // `None` if the records couldn't be read, in which case they're left alone.
+    records: Option<Records>,
 }
 
 impl Window {
-    pub fn new(title: &str, width: u32, height: u32, game: Minesweeper) -> Result<Box<Self>> {
+    pub fn new(title: &str, width: u32, height: u32, mut game: Minesweeper) -> Result<Box<Self>> {
         let instance = unsafe { GetModuleHandleW(None)? };
         REGISTER_WINDOW_CLASS.call_once(|| {
             let class = WNDCLASSW {
@@ -57,9 +76,23 @@ impl Window {
             (rect.right - rect.left, rect.bottom - rect.top)
         };
 
+        let (sender, events) = mpsc::channel();
+        game.add_listener(move |event| {
+            // The window only goes away together with the game.
+            _ = sender.send(*event);
+        });
+
+        // Only the window can export boards.
+        let mut key_bindings = KeyBindings::default();
+        key_bindings.bind(Key::Ctrl('e'), Command::ExportBoard);
+
         let mut result = Box::new(Self {
             handle: HWND::default(),
+            title: title.to_owned(),
             game,
+            events,
+            key_bindings,
+            records: records::load().ok(),
         });
 
         let hinstance: HINSTANCE = instance.into();
@@ -67,7 +100,7 @@ impl Window {
             CreateWindowExW(
                 window_ex_style,
                 WINDOW_CLASS_NAME,
-                &HSTRING::from(title),
+                &HSTRING::from(result.title_with_seed()),
                 window_style,
                 CW_USEDEFAULT,
                 CW_USEDEFAULT,
@@ -80,6 +113,16 @@ impl Window {
             )?
         };
         unsafe { _ = ShowWindow(window, SW_SHOW) };
+        if result.game.is_replaying() {
+            unsafe {
+                SetTimer(
+                    Some(window),
+                    REPLAY_TIMER_ID,
+                    REPLAY_TIMER_INTERVAL_MS,
+                    None,
+                )
+            };
+        }
 
         Ok(result)
     }
@@ -101,9 +144,119 @@ impl Window {
         unsafe { compositor_desktop.CreateDesktopWindowTarget(self.handle(), is_topmost) }
     }
 
+    // Every game shows its seed so that a board can be reproduced later, the
+    // lives it has left if it has more than one, and how it went once it's over.
+    fn title_with_seed(&self) -> String {
+        let title = format!("{} (seed {})", self.title, self.game.seed());
+        let stats = match self.game.game_stats() {
+            Some(stats) => stats,
+            None if self.game.lives() > 1 => {
+                return format!("{} - lives left: {}", title, self.game.lives_left())
+            }
+            None => return title,
+        };
+        let title = format!("{} - {}", title, stats);
+
+        match &self.records {
+            Some(records) => {
+                let config_records =
+                    records.for_config(&self.game.config(), &self.game.record_rules());
+                let best_time = match config_records.best_time {
+                    Some(time) => format!("{:.2}s", time.as_secs_f64()),
+                    None => "-".to_owned(),
+                };
+                format!(
+                    "{} - best {}, won {}/{}, streak {} (best {})",
+                    title,
+                    best_time,
+                    config_records.won,
+                    config_records.played,
+                    config_records.current_streak,
+                    config_records.best_streak
+                )
+            }
+            None => title,
+        }
+    }
+
+    fn update_title(&self) -> Result<()> {
+        unsafe { SetWindowTextW(self.handle, &HSTRING::from(self.title_with_seed())) }
+    }
+
+    fn export_board(&self) {
+        if let Some(board) = self.game.mbf_board() {
+            // There's no UI for errors yet, the board is just not exported.
+            _ = mbf::store(&board);
+        }
+    }
+
+    // Catches up with what happened in the game since the last input.
+    fn on_game_events(&mut self) -> Result<()> {
+        let mut game_ended = false;
+        for event in self.events.try_iter() {
+            game_ended |= matches!(
+                event,
+                GameEvent::GameWon { .. } | GameEvent::GameLost { .. }
+            );
+        }
+        if game_ended {
+            self.store_finished_game();
+        }
+        // Undo changes the title too, without an event of its own.
+        self.update_title()
+    }
+
+    // Keeps the recording and the record of every game that ends.
+    fn store_finished_game(&mut self) {
+        // Failing to store either shouldn't interrupt the game.
+        if let Some(replay) = self.game.take_finished_replay() {
+            _ = replay::store(&replay);
+        }
+        if let Some(record) = self.game.take_finished_record() {
+            if let Some(records) = &mut self.records {
+                _ = records::append(&record);
+                records.push(record);
+            }
+        }
+    }
+
+    fn on_replay_timer(&mut self) -> Result<()> {
+        if self.game.advance_replay()? {
+            return Ok(());
+        }
+
+        unsafe { KillTimer(Some(self.handle), REPLAY_TIMER_ID)? };
+        // The replay played at the size it was recorded at, so catch up with the window.
+        let size = self.size()?;
+        let size = Vector2 {
+            X: size.Width as f32,
+            Y: size.Height as f32,
+        };
+        self.game.on_parent_size_changed(&size)
+    }
+
     fn message_handler(&mut self, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        // The replay drives the game on its own while it plays, so the
+        // player's input is ignored until it's done.
+        let is_input = matches!(
+            message,
+            WM_MOUSEMOVE | WM_SIZE | WM_SIZING | WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_KEYDOWN
+        );
+        if is_input && self.game.is_replaying() {
+            return unsafe { DefWindowProcW(self.handle, message, wparam, lparam) };
+        }
+
         match message {
             WM_DESTROY => {
+                // Failing to save shouldn't keep the window from closing. Just
+                // watching a replay or playing a loaded board leaves the save
+                // of an earlier game alone.
+                if self.game.has_played_own_game() {
+                    _ = match self.game.saved_game() {
+                        Some(saved) => save::store(&saved),
+                        None => save::discard(),
+                    };
+                }
                 unsafe { PostQuitMessage(0) };
                 return LRESULT(0);
             }
@@ -125,9 +278,28 @@ impl Window {
             }
             WM_LBUTTONDOWN => {
                 self.game.on_pointer_pressed(false, false).unwrap();
+                self.on_game_events().unwrap();
             }
             WM_RBUTTONDOWN => {
                 self.game.on_pointer_pressed(true, false).unwrap();
+                self.on_game_events().unwrap();
+            }
+            WM_KEYDOWN => {
+                let is_control_down = unsafe { GetKeyState(VK_CONTROL.0 as i32) } < 0;
+                if let Some(command) = key_from_virtual_key(wparam.0 as u16, is_control_down)
+                    .and_then(|key| self.key_bindings.command(key))
+                {
+                    match command {
+                        Command::ExportBoard => self.export_board(),
+                        Command::Quit => unsafe { DestroyWindow(self.handle) }.unwrap(),
+                        command => self.game.on_command(command).unwrap(),
+                    }
+                    self.on_game_events().unwrap();
+                }
+            }
+            WM_TIMER if wparam.0 == REPLAY_TIMER_ID => {
+                self.on_replay_timer().unwrap();
+                self.on_game_events().unwrap();
             }
             _ => {}
         }
@@ -170,6 +342,26 @@ fn get_window_size(window_handle: HWND) -> Result<SizeInt32> {
     }
 }
 
+// Letters, digits and space have the same virtual key code as their
+// character.
+fn key_from_virtual_key(key: u16, is_control_down: bool) -> Option<Key> {
+    let key = match key {
+        key if key == VK_UP.0 => Some(Key::Up),
+        key if key == VK_DOWN.0 => Some(Key::Down),
+        key if key == VK_LEFT.0 => Some(Key::Left),
+        key if key == VK_RIGHT.0 => Some(Key::Right),
+        key if key == VK_RETURN.0 => Some(Key::Enter),
+        key if key == VK_ESCAPE.0 => Some(Key::Escape),
+        key if (VK_F1.0..=VK_F24.0).contains(&key) => Some(Key::F((key - VK_F1.0 + 1) as u8)),
+        0x20 | 0x30..=0x39 | 0x41..=0x5A => char::from_u32(key as u32).map(Key::Char),
+        _ => None,
+    };
+    match key {
+        Some(Key::Char(c)) if is_control_down => Some(Key::Ctrl(c)),
+        key => key,
+    }
+}
+
 fn get_mouse_position(lparam: LPARAM) -> (isize, isize) {
     let x = lparam.0 & 0xffff;
     let y = (lparam.0 >> 16) & 0xffff;